@@ -1,3 +1,5 @@
+#![allow(clippy::vec_init_then_push)]
+
 use richparse::{RichString, Color, Style, rich};
 
 fn main() {  
@@ -0,0 +1,189 @@
+//! # richparse-macros
+//!
+//! Compile-time companions to the [`richparse`](https://docs.rs/richparse) macros.
+//!
+//! The markup literal is parsed in strict mode while your crate compiles, so a
+//! typo such as `<bodl>` or `<color=#GG0000>` is a compile error instead of
+//! garbled output at runtime. The expansion contains the already-parsed spans,
+//! so there is no parsing cost at runtime.
+//!
+//! ```rust
+//! use richparse_macros::{ansi, rich};
+//!
+//! let greeting = rich!("<green><bold>Success!</bold></green>");
+//! assert_eq!(greeting, richparse::RichString::parse("<green><bold>Success!</bold></green>"));
+//!
+//! const ERROR: &str = ansi!("<red><bold>error</bold></red>");
+//! println!("{}: something went wrong", ERROR);
+//! ```
+//!
+//! Invalid markup is rejected at compile time:
+//!
+//! ```compile_fail
+//! let oops = richparse_macros::rich!("<bodl>Oops</bodl>");
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span as TokenSpan, TokenStream as TokenStream2};
+use quote::quote;
+use richparse::{Color, ParseError, RichString, Span, Style};
+use syn::{LitStr, parse_macro_input};
+
+/// Parses markup at compile time and expands to a `richparse::RichString<'static>`.
+///
+/// The markup must be a string literal. It is validated with
+/// `RichString::try_parse`, so unknown tags, invalid colors and unbalanced
+/// tags are reported as compile errors.
+#[proc_macro]
+pub fn rich(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    let markup = lit.value();
+
+    match RichString::try_parse(&markup) {
+        Ok(rs) => {
+            let spans = rs.spans.iter().map(span_tokens);
+            quote! {
+                ::richparse::RichString::new(::std::vec![#(#spans),*])
+            }
+            .into()
+        }
+        Err(err) => error_tokens(&lit, &err).into(),
+    }
+}
+
+/// Parses markup at compile time and expands to the rendered ANSI `&'static str`.
+///
+/// Use this for fixed banners and labels; the output is rendered once with the
+/// default settings and does not adapt to the terminal it is printed on.
+#[proc_macro]
+pub fn ansi(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    let markup = lit.value();
+
+    match RichString::try_parse(&markup) {
+        Ok(rs) => {
+            let rendered = rs.to_string();
+            quote!(#rendered).into()
+        }
+        Err(err) => error_tokens(&lit, &err).into(),
+    }
+}
+
+fn error_tokens(lit: &LitStr, err: &ParseError) -> TokenStream2 {
+    let range = err.span();
+    let markup = lit.value();
+    let snippet = markup.get(range.clone()).unwrap_or_default();
+    let message = format!("invalid markup: {} (`{}`)", err, snippet);
+    syn::Error::new(error_span(lit, range), message).to_compile_error()
+}
+
+/// Points at the offending bytes inside the literal when the compiler allows it,
+/// falling back to the whole literal.
+fn error_span(lit: &LitStr, range: std::ops::Range<usize>) -> TokenSpan {
+    let token = lit.token();
+    let source = token.to_string();
+
+    // Byte offsets only line up with the source when the literal is a plain
+    // "..." string without escape sequences.
+    if source.len() == lit.value().len() + 2
+        && source[1..source.len() - 1] == lit.value()
+        && let Some(span) = token.subspan(range.start + 1..range.end + 1)
+    {
+        return span;
+    }
+    lit.span()
+}
+
+fn span_tokens(span: &Span<'_>) -> TokenStream2 {
+    let text = span.text.as_ref();
+    let style = style_tokens(&span.style);
+    if span.is_rule() {
+        return quote! {
+            ::richparse::Span::rule(#style)
+        };
+    }
+    quote! {
+        ::richparse::Span::new(#text, #style)
+    }
+}
+
+fn style_tokens(style: &Style<'_>) -> TokenStream2 {
+    let Style {
+        fg,
+        bg,
+        bold,
+        italic,
+        underline,
+        double_underline,
+        curly_underline,
+        overline,
+        strikethrough,
+        dim,
+        blink,
+        inverse,
+        hidden,
+        url,
+        underline_color,
+    } = style;
+
+    let fg = color_tokens(fg);
+    let bg = color_tokens(bg);
+    let url = match url {
+        Some(url) => {
+            let url = url.as_ref();
+            quote!(::core::option::Option::Some(::std::borrow::Cow::Borrowed(#url)))
+        }
+        None => quote!(::core::option::Option::None),
+    };
+    let underline_color = match underline_color {
+        Some(color) => {
+            let color = color_tokens(color);
+            quote!(::core::option::Option::Some(#color))
+        }
+        None => quote!(::core::option::Option::None),
+    };
+
+    quote! {
+        ::richparse::Style {
+            fg: #fg,
+            bg: #bg,
+            bold: #bold,
+            italic: #italic,
+            underline: #underline,
+            double_underline: #double_underline,
+            curly_underline: #curly_underline,
+            overline: #overline,
+            strikethrough: #strikethrough,
+            dim: #dim,
+            blink: #blink,
+            inverse: #inverse,
+            hidden: #hidden,
+            url: #url,
+            underline_color: #underline_color,
+        }
+    }
+}
+
+fn color_tokens(color: &Color) -> TokenStream2 {
+    match color {
+        Color::Black => quote!(::richparse::Color::Black),
+        Color::Red => quote!(::richparse::Color::Red),
+        Color::Green => quote!(::richparse::Color::Green),
+        Color::Yellow => quote!(::richparse::Color::Yellow),
+        Color::Blue => quote!(::richparse::Color::Blue),
+        Color::Magenta => quote!(::richparse::Color::Magenta),
+        Color::Cyan => quote!(::richparse::Color::Cyan),
+        Color::White => quote!(::richparse::Color::White),
+        Color::BrightBlack => quote!(::richparse::Color::BrightBlack),
+        Color::BrightRed => quote!(::richparse::Color::BrightRed),
+        Color::BrightGreen => quote!(::richparse::Color::BrightGreen),
+        Color::BrightYellow => quote!(::richparse::Color::BrightYellow),
+        Color::BrightBlue => quote!(::richparse::Color::BrightBlue),
+        Color::BrightMagenta => quote!(::richparse::Color::BrightMagenta),
+        Color::BrightCyan => quote!(::richparse::Color::BrightCyan),
+        Color::BrightWhite => quote!(::richparse::Color::BrightWhite),
+        Color::Default => quote!(::richparse::Color::Default),
+        Color::Ansi256(n) => quote!(::richparse::Color::Ansi256(#n)),
+        Color::Rgb(r, g, b) => quote!(::richparse::Color::Rgb(#r, #g, #b)),
+    }
+}
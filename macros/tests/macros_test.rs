@@ -0,0 +1,22 @@
+use richparse::{Color, RichString, Style};
+use richparse_macros::{ansi, rich};
+
+#[test]
+fn test_rich_matches_runtime_parse() {
+    let markup = "Hello <red><b>World</b></red> <link=https://example.com>link</link> <<tag";
+    assert_eq!(rich!("Hello <red><b>World</b></red> <link=https://example.com>link</link> <<tag"), RichString::parse(markup));
+}
+
+#[test]
+fn test_rich_extended_colors() {
+    let rs = rich!("<color=#FF8800>Orange</color><bg=123>Cyan</bg><cu=bright red>Curly</cu>");
+    assert_eq!(rs.spans[0].style.fg, Color::Rgb(255, 136, 0));
+    assert_eq!(rs.spans[1].style.bg, Color::Ansi256(123));
+    assert_eq!(rs.spans[2].style, Style::new().curly_underline().underline_color(Color::BrightRed));
+}
+
+#[test]
+fn test_ansi_matches_runtime_render() {
+    const OUT: &str = ansi!("<green>Ok</green>");
+    assert_eq!(OUT, RichString::parse("<green>Ok</green>").to_string());
+}
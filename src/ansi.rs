@@ -0,0 +1,286 @@
+//! Interpreting text that already contains ANSI escape sequences.
+
+use std::borrow::Cow;
+use crate::parser::RichString;
+use crate::span::Span;
+use crate::style::{Color, Style};
+
+const ESC: char = '\x1b';
+
+/// Parses text containing SGR sequences and OSC 8 hyperlinks into spans.
+///
+/// Text is borrowed from `input`. Other escape sequences, such as cursor
+/// movement, are dropped, as is an escape sequence cut off at the end of the input.
+pub(crate) fn parse_ansi(input: &str) -> RichString<'_> {
+    let mut spans = Vec::new();
+    let mut style = Style::default();
+    let mut text_start = 0;
+    let mut cursor = 0;
+
+    while let Some(pos) = input[cursor..].find(ESC) {
+        let esc = cursor + pos;
+        if esc > text_start {
+            spans.push(Span::new(&input[text_start..esc], style.clone()));
+        }
+
+        let rest = &input[esc + 1..];
+        let consumed = match rest.chars().next() {
+            Some('[') => match csi_end(&rest[1..]) {
+                Some((params, final_byte, len)) => {
+                    if final_byte == 'm' {
+                        apply_sgr(params, &mut style);
+                    }
+                    1 + len
+                }
+                None => rest.len(),
+            },
+            Some(']') => match osc_end(&rest[1..]) {
+                Some((body, len)) => {
+                    if let Some(link) = body.strip_prefix("8;") {
+                        // The URL follows the (usually empty) parameter list
+                        let url = link.split_once(';').map_or("", |(_, url)| url);
+                        style.url = (!url.is_empty()).then_some(Cow::Borrowed(url));
+                    }
+                    1 + len
+                }
+                None => rest.len(),
+            },
+            // Other escapes: any intermediate bytes, then a final byte (e.g. ESC ( B)
+            Some(_) => {
+                let intermediates = rest.find(|c: char| !(' '..='/').contains(&c)).unwrap_or(rest.len());
+                intermediates + rest[intermediates..].chars().next().map_or(0, char::len_utf8)
+            }
+            None => 0,
+        };
+
+        cursor = esc + 1 + consumed;
+        text_start = cursor;
+    }
+
+    if text_start < input.len() {
+        spans.push(Span::new(&input[text_start..], style));
+    }
+    RichString::new(spans)
+}
+
+/// Returns the length in bytes of the escape sequence at the start of `text`,
+/// which must begin with ESC. A sequence cut off by the end of `text` extends to the end.
+pub(crate) fn escape_len(text: &str) -> usize {
+    let rest = &text[1..];
+    let len = match rest.chars().next() {
+        Some('[') => csi_end(&rest[1..]).map_or(rest.len(), |(_, _, len)| 1 + len),
+        Some(']') => osc_end(&rest[1..]).map_or(rest.len(), |(_, len)| 1 + len),
+        Some(_) => {
+            let intermediates = rest.find(|c: char| !(' '..='/').contains(&c)).unwrap_or(rest.len());
+            intermediates + rest[intermediates..].chars().next().map_or(0, char::len_utf8)
+        }
+        None => 0,
+    };
+    1 + len
+}
+
+/// Finds the end of a CSI sequence, given the text after `ESC [`.
+///
+/// Returns the parameter bytes, the final byte, and the length of the sequence.
+fn csi_end(rest: &str) -> Option<(&str, char, usize)> {
+    let end = rest.find(|c: char| ('\x40'..='\x7e').contains(&c))?;
+    let final_byte = rest[end..].chars().next()?;
+    Some((&rest[..end], final_byte, end + 1))
+}
+
+/// Finds the end of an OSC sequence, given the text after `ESC ]`.
+///
+/// The sequence ends at BEL or at the string terminator `ESC \`. Returns the
+/// body and the length including the terminator.
+fn osc_end(rest: &str) -> Option<(&str, usize)> {
+    let end = rest.find(['\x07', ESC])?;
+    if rest[end..].starts_with('\x07') {
+        Some((&rest[..end], end + 1))
+    } else if rest[end + 1..].starts_with('\\') {
+        Some((&rest[..end], end + 2))
+    } else {
+        None
+    }
+}
+
+/// Applies the SGR parameters `params` (e.g. `1;38;5;208`) to `style`.
+fn apply_sgr(params: &str, style: &mut Style<'_>) {
+    let params: Vec<&str> = params.split(';').collect();
+    let mut i = 0;
+    while i < params.len() {
+        let param = params[i];
+        i += 1;
+
+        // Colon-separated sub-parameters, e.g. 4:3 or 38:2::255:0:0
+        if param.contains(':') {
+            let sub: Vec<&str> = param.split(':').collect();
+            match sub[0] {
+                "4" => set_underline(style, sub.get(1).and_then(|s| s.parse().ok()).unwrap_or(1)),
+                target @ ("38" | "48" | "58") => {
+                    let mut values = sub[1..].iter().map(|s| s.parse::<u8>().ok());
+                    let color = match values.next().flatten() {
+                        Some(5) => values.next().flatten().map(Color::Ansi256),
+                        Some(2) => {
+                            // An optional color space id precedes the components
+                            let rest: Vec<Option<u8>> = values.collect();
+                            let rgb = if rest.len() >= 4 { &rest[rest.len() - 3..] } else { &rest[..] };
+                            match rgb {
+                                [Some(r), Some(g), Some(b)] => Some(Color::Rgb(*r, *g, *b)),
+                                _ => None,
+                            }
+                        }
+                        _ => None,
+                    };
+                    if let (Some(color), Ok(target)) = (color, target.parse()) {
+                        set_color(style, target, color);
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let Ok(code) = (if param.is_empty() { Ok(0) } else { param.parse::<u16>() }) else {
+            continue;
+        };
+        match code {
+            0 => {
+                let url = style.url.take();
+                *style = Style::default();
+                style.url = url;
+            }
+            1 => style.bold = true,
+            2 => style.dim = true,
+            3 => style.italic = true,
+            4 => set_underline(style, 1),
+            5 | 6 => style.blink = true,
+            7 => style.inverse = true,
+            8 => style.hidden = true,
+            9 => style.strikethrough = true,
+            21 => set_underline(style, 2),
+            22 => {
+                style.bold = false;
+                style.dim = false;
+            }
+            23 => style.italic = false,
+            24 => set_underline(style, 0),
+            25 => style.blink = false,
+            27 => style.inverse = false,
+            28 => style.hidden = false,
+            29 => style.strikethrough = false,
+            30..=37 => style.fg = Color::from_ansi16((code - 30) as u8),
+            39 => style.fg = Color::Default,
+            40..=47 => style.bg = Color::from_ansi16((code - 40) as u8),
+            49 => style.bg = Color::Default,
+            53 => style.overline = true,
+            55 => style.overline = false,
+            59 => style.underline_color = None,
+            90..=97 => style.fg = Color::from_ansi16((code - 90 + 8) as u8),
+            100..=107 => style.bg = Color::from_ansi16((code - 100 + 8) as u8),
+            38 | 48 | 58 => {
+                let color = match params.get(i).copied() {
+                    Some("5") => {
+                        let color = params.get(i + 1).and_then(|n| n.parse().ok()).map(Color::Ansi256);
+                        i += 2;
+                        color
+                    }
+                    Some("2") => {
+                        let rgb: Vec<Option<u8>> = (1..=3).map(|k| params.get(i + k).and_then(|n| n.parse().ok())).collect();
+                        i += 4;
+                        match rgb[..] {
+                            [Some(r), Some(g), Some(b)] => Some(Color::Rgb(r, g, b)),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(color) = color {
+                    set_color(style, code, color);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Sets the color selected by SGR `target` (38, 48 or 58).
+fn set_color(style: &mut Style<'_>, target: u16, color: Color) {
+    match target {
+        38 => style.fg = color,
+        48 => style.bg = color,
+        // Basic underline colors are written as 58;5;n, so read them back as such
+        _ => {
+            style.underline_color = Some(match color {
+                Color::Ansi256(n) if n < 16 => Color::from_ansi16(n),
+                color => color,
+            })
+        }
+    }
+}
+
+/// Sets the underline kind from the `4:n` sub-parameter: 0 none, 2 double,
+/// 3 curly, anything else (single, dotted, dashed) a single underline.
+fn set_underline(style: &mut Style<'_>, kind: u8) {
+    style.underline = !matches!(kind, 0 | 2 | 3);
+    style.double_underline = kind == 2;
+    style.curly_underline = kind == 3;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_ansi_basic() {
+        let rs = RichString::from_ansi("\x1b[1;31mError:\x1b[0m plain \x1b[38;5;208;48;2;1;2;3mx\x1b[39;49m");
+        assert_eq!(rs.spans.len(), 3);
+        assert_eq!(rs.spans[0].text, "Error:");
+        assert_eq!(rs.spans[0].style, Style::new().fg(Color::Red).bold());
+        assert_eq!(rs.spans[1].text, " plain ");
+        assert_eq!(rs.spans[1].style, Style::default());
+        assert_eq!(rs.spans[2].style, Style::new().fg(Color::Ansi256(208)).bg(Color::Rgb(1, 2, 3)));
+    }
+
+    #[test]
+    fn test_from_ansi_borrows() {
+        let input = "\x1b[32mok\x1b[0m";
+        let rs = RichString::from_ansi(input);
+        assert!(matches!(rs.spans[0].text, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_from_ansi_underlines() {
+        let rs = RichString::from_ansi("\x1b[4:3;58;5;1ma\x1b[4:2;58:2::9:8:7mb\x1b[24;21mc\x1b[24;53md");
+        assert_eq!(rs.spans[0].style, Style::new().curly_underline().underline_color(Color::Red));
+        assert_eq!(rs.spans[1].style, Style::new().double_underline().underline_color(Color::Rgb(9, 8, 7)));
+        assert_eq!(rs.spans[2].style, Style::new().double_underline().underline_color(Color::Rgb(9, 8, 7)));
+        assert_eq!(rs.spans[3].style, Style::new().overline().underline_color(Color::Rgb(9, 8, 7)));
+    }
+
+    #[test]
+    fn test_from_ansi_hyperlinks() {
+        let rs = RichString::from_ansi("\x1b]8;;https://x.dev\x1b\\link\x1b]8;;\x1b\\ \x1b]8;id=1;https://y.dev\x07y\x1b]8;;\x07");
+        assert_eq!(rs.spans[0].style.url.as_deref(), Some("https://x.dev"));
+        assert_eq!(rs.spans[1].style.url, None);
+        assert_eq!(rs.spans[2].text, "y");
+        assert_eq!(rs.spans[2].style.url.as_deref(), Some("https://y.dev"));
+    }
+
+    #[test]
+    fn test_from_ansi_ignores_other_sequences() {
+        let rs = RichString::from_ansi("\x1b[2K\x1b[1Gab\x1b(Bc\x1b]0;title\x07d\x1b[3");
+        assert_eq!(rs.plain().to_string(), "abcd");
+    }
+
+    #[test]
+    fn test_from_ansi_round_trip() {
+        let rs = RichString::parse(
+            "<b><red>a</red><bg=#102030>b</bg></b><cu=blue><i>c</i></cu><link=https://x.dev><dim>d</dim></link><inverse>e</inverse>",
+        );
+        let rendered = rs.to_string();
+        let parsed = RichString::from_ansi(&rendered);
+        let styles: Vec<_> = parsed.spans.iter().map(|s| (s.text.to_string(), s.style.clone())).collect();
+        let expected: Vec<_> = rs.spans.iter().map(|s| (s.text.to_string(), s.style.clone())).collect();
+        assert_eq!(styles, expected);
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::ops::Range;
+
+/// An error produced by [`RichString::try_parse`](crate::RichString::try_parse).
+///
+/// Every variant carries the byte range of the offending markup in the input,
+/// so callers can point at the exact location of the problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A tag whose name is not recognized, e.g. `<bodl>`.
+    UnknownTag { tag: String, span: Range<usize> },
+    /// A color value that could not be parsed, e.g. `<color=#GG0000>`.
+    InvalidColor { value: String, span: Range<usize> },
+    /// A word in a style expression that is neither a color nor a modifier,
+    /// e.g. `bodl` in `bodl red`.
+    UnknownStyle { word: String, span: Range<usize> },
+    /// A closing tag with no open tag to close, e.g. a stray `</red>`.
+    UnbalancedClose { tag: String, span: Range<usize> },
+    /// A closing tag that does not match the most recent open tag,
+    /// e.g. `</red>` in `<red><b>text</red>`.
+    MismatchedClose { expected: String, found: String, span: Range<usize> },
+    /// A tag that was opened but never closed before the end of the input.
+    UnclosedTag { tag: String, span: Range<usize> },
+    /// A `<` that is neither escaped (`<<`) nor terminated by a `>`.
+    UnterminatedTag { span: Range<usize> },
+}
+
+impl ParseError {
+    /// Returns the byte range of the offending markup in the input.
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            ParseError::UnknownTag { span, .. }
+            | ParseError::InvalidColor { span, .. }
+            | ParseError::UnknownStyle { span, .. }
+            | ParseError::UnbalancedClose { span, .. }
+            | ParseError::MismatchedClose { span, .. }
+            | ParseError::UnclosedTag { span, .. }
+            | ParseError::UnterminatedTag { span } => span.clone(),
+        }
+    }
+
+    /// Moves the span of this error to where `map` puts it.
+    pub(crate) fn map_span(mut self, map: impl FnOnce(Range<usize>) -> Range<usize>) -> Self {
+        match &mut self {
+            ParseError::UnknownTag { span, .. }
+            | ParseError::InvalidColor { span, .. }
+            | ParseError::UnknownStyle { span, .. }
+            | ParseError::UnbalancedClose { span, .. }
+            | ParseError::MismatchedClose { span, .. }
+            | ParseError::UnclosedTag { span, .. }
+            | ParseError::UnterminatedTag { span } => {
+                *span = map(span.clone());
+            }
+        }
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnknownTag { tag, span } => {
+                write!(f, "unknown tag `{}` at {}..{}", tag, span.start, span.end)
+            }
+            ParseError::InvalidColor { value, span } => {
+                write!(f, "invalid color `{}` at {}..{}", value, span.start, span.end)
+            }
+            ParseError::UnknownStyle { word, span } => {
+                write!(f, "unknown style `{}` at {}..{}", word, span.start, span.end)
+            }
+            ParseError::UnbalancedClose { tag, span } => {
+                write!(f, "closing tag `</{}>` at {}..{} has no matching open tag", tag, span.start, span.end)
+            }
+            ParseError::MismatchedClose { expected, found, span } => {
+                write!(f, "closing tag `</{}>` at {}..{} does not match open tag `<{}>`", found, span.start, span.end, expected)
+            }
+            ParseError::UnclosedTag { tag, span } => {
+                write!(f, "tag `<{}>` at {}..{} is never closed", tag, span.start, span.end)
+            }
+            ParseError::UnterminatedTag { span } => {
+                write!(f, "unterminated `<` at {}..{}", span.start, span.end)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// An error produced while loading or applying a [`Theme`](crate::Theme).
+#[derive(Debug)]
+pub enum ThemeError {
+    /// The style expression for a tag could not be parsed.
+    InvalidStyle { tag: String, error: ParseError },
+    /// A theme inherits from a theme that was not provided.
+    UnknownParent(String),
+    /// Following `inherits` leads back to a theme already visited.
+    InheritanceCycle(String),
+    #[cfg(feature = "theme")]
+    Io(std::io::Error),
+    #[cfg(feature = "theme")]
+    Toml(toml::de::Error),
+    #[cfg(feature = "theme")]
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::InvalidStyle { tag, error } => write!(f, "invalid style for tag `{}`: {}", tag, error),
+            ThemeError::UnknownParent(name) => write!(f, "unknown parent theme `{}`", name),
+            ThemeError::InheritanceCycle(name) => write!(f, "inheritance cycle through theme `{}`", name),
+            #[cfg(feature = "theme")]
+            ThemeError::Io(e) => write!(f, "failed to read theme: {}", e),
+            #[cfg(feature = "theme")]
+            ThemeError::Toml(e) => write!(f, "invalid TOML theme: {}", e),
+            #[cfg(feature = "theme")]
+            ThemeError::Json(e) => write!(f, "invalid JSON theme: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ThemeError::InvalidStyle { error, .. } => Some(error),
+            #[cfg(feature = "theme")]
+            ThemeError::Io(e) => Some(e),
+            #[cfg(feature = "theme")]
+            ThemeError::Toml(e) => Some(e),
+            #[cfg(feature = "theme")]
+            ThemeError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
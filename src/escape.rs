@@ -0,0 +1,120 @@
+//! Escaping text for inclusion in markup.
+
+use std::borrow::Cow;
+use std::fmt;
+use crate::width::{graphemes, str_width};
+
+/// Escapes `text` so that it parses as literal text, whatever it contains.
+///
+/// Every `<` is doubled, which the parser reads back as a single `<`; nothing
+/// else needs escaping. Escaped text can be placed anywhere between tags, and
+/// a `<` at its end cannot combine with a following tag.
+///
+/// ```rust
+/// use richparse::{escape, RichString};
+///
+/// let name = "<script>.txt";
+/// let markup = format!("<red>{}</red>", escape(name));
+/// assert_eq!(markup, "<red><<script>.txt</red>");
+/// assert_eq!(RichString::parse(&markup).plain().to_string(), name);
+/// ```
+pub fn escape(text: &str) -> Cow<'_, str> {
+    if text.contains('<') {
+        Cow::Owned(text.replace('<', "<<"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Reverses [`escape`], turning each `<<` back into `<`.
+///
+/// A single `<` is kept as it is, matching how the parser treats a `<` that
+/// does not start a tag. Tags are not removed; this is not a markup stripper.
+pub fn unescape(text: &str) -> Cow<'_, str> {
+    if text.contains("<<") {
+        Cow::Owned(text.replace("<<", "<"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Displays a value escaped for inclusion in markup; see [`escape`].
+///
+/// Width, fill, alignment and precision apply to the unescaped text, so the
+/// parsed result lines up as requested. As for a [`RichString`](crate::RichString),
+/// the width and precision are counted in terminal columns.
+///
+/// ```rust
+/// use richparse::{Escaped, RichString};
+///
+/// let markup = format!("<b>{:>4}</b>", Escaped("<3"));
+/// assert_eq!(markup, "<b>  <<3</b>");
+/// assert_eq!(RichString::parse(&markup).plain().to_string(), "  <3");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Escaped<T>(pub T);
+
+impl<T: fmt::Display> fmt::Display for Escaped<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = self.0.to_string();
+        if f.width().is_none() && f.precision().is_none() {
+            return f.write_str(&escape(&text));
+        }
+
+        let text = match f.precision() {
+            Some(precision) => {
+                let mut columns = 0;
+                graphemes(&text)
+                    .take_while(|(_, width)| {
+                        columns += width;
+                        columns <= precision
+                    })
+                    .map(|(grapheme, _)| grapheme)
+                    .collect()
+            }
+            None => text,
+        };
+        let padding = f.width().unwrap_or(0).saturating_sub(str_width(&text));
+        let (before, after) = match f.align() {
+            Some(fmt::Alignment::Right) => (padding, 0),
+            Some(fmt::Alignment::Center) => (padding / 2, padding - padding / 2),
+            Some(fmt::Alignment::Left) | None => (0, padding),
+        };
+        let fill = escape(f.fill().encode_utf8(&mut [0; 4])).into_owned();
+        f.write_str(&fill.repeat(before))?;
+        f.write_str(&escape(&text))?;
+        f.write_str(&fill.repeat(after))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::RichString;
+
+    #[test]
+    fn test_escape_round_trip() {
+        for text in ["", "plain", "<", "<<", "a<", "<b>x</b>", "1 < 2 <<3>", "<link=x>"] {
+            let escaped = escape(text);
+            assert_eq!(unescape(&escaped), text);
+            assert_eq!(RichString::try_parse(&escaped).unwrap().plain().to_string(), text);
+            // Escaped text next to tags never changes how they parse
+            let markup = format!("<red>{}</red>{}<b>x</b>", escaped, escaped);
+            let rs = RichString::try_parse(&markup).unwrap();
+            assert_eq!(rs.plain().to_string(), format!("{}{}x", text, text));
+        }
+        assert!(matches!(escape("plain"), Cow::Borrowed(_)));
+        assert_eq!(unescape("a < b"), "a < b");
+    }
+
+    #[test]
+    fn test_escaped_formatting() {
+        assert_eq!(format!("{}", Escaped("<b>")), "<<b>");
+        assert_eq!(format!("{:<<4}|", Escaped(1)), "1<<<<<<|");
+        assert_eq!(format!("{:^5.2}", Escaped("<<<")), " <<<<  ");
+        assert_eq!(format!("{:>6}|", Escaped("日本<")), " 日本<<|");
+        assert_eq!(format!("{:.2}", Escaped("日本語")), "日");
+        assert_eq!(format!("{:.3}|", Escaped("日本語")), "日|");
+        assert_eq!(format!("{:.5}", Escaped("日<本語")), "日<<本");
+    }
+}
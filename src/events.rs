@@ -0,0 +1,287 @@
+//! A pull parser exposing the structure of markup as a stream of events.
+//!
+//! [`RichString::parse`](crate::RichString::parse) resolves markup into styled
+//! spans; [`Events`] reports what was written instead, token by token, using
+//! the same tokenizer. It is meant for tools that work on the markup itself,
+//! such as linters, formatters and custom renderers.
+//!
+//! ```rust
+//! use richparse::events::{Event, Events};
+//!
+//! let names: Vec<String> = Events::new("<red>a <b>b</b></red><br>")
+//!     .filter_map(|event| match event {
+//!         Event::Start(tag) => Some(format!("+{}", tag.name())),
+//!         Event::End(tag) => Some(format!("-{}", tag.name())),
+//!         Event::Empty(tag) => Some(tag.name()),
+//!         _ => None,
+//!     })
+//!     .collect();
+//! assert_eq!(names, ["+color", "+bold", "-bold", "-color", "br"]);
+//! ```
+
+use std::borrow::Cow;
+use std::ops::Range;
+use crate::error::ParseError;
+use crate::parser::{
+    apply_tag, canonical_tag_name, inline_element, parse_attributes, split_self_closing, Token, Tokens,
+};
+use crate::registry::TagRegistry;
+use crate::style::Style;
+
+/// A piece of markup, as produced by [`Events`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// An opening tag such as `<red>` or `<color=red bold>`.
+    Start(Tag<'a>),
+    /// A closing tag such as `</red>`, or `</>` with an empty name. It is not
+    /// checked against the open tags.
+    End(Tag<'a>),
+    /// A tag without content: a self-closing tag such as `<b/>`, or a built-in
+    /// inline element such as `<br>` or `<hr/>` that is not overridden by the
+    /// registry given to [`Events::with_registry`].
+    Empty(Tag<'a>),
+    /// Text between tags.
+    Text(&'a str),
+    /// An escaped `<<`, standing for a literal `<`.
+    Escaped,
+    /// A `<` without a matching `>`, which is parsed as a literal `<` or
+    /// reported as an error in strict mode.
+    Unterminated,
+}
+
+/// A tag as written in the markup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag<'a> {
+    content: &'a str,
+    /// The position of `content` in the input.
+    offset: usize,
+    span: Range<usize>,
+}
+
+impl<'a> Tag<'a> {
+    /// The text between the brackets, without the `/` of a closing or
+    /// self-closing tag: `color=red` for `<color=red>`, `red` for `</red>`.
+    pub fn as_str(&self) -> &'a str {
+        self.content
+    }
+
+    /// The byte range of the whole tag in the input, brackets included.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// The lowercase name that closing tags are matched by, with aliases
+    /// folded together: `bold` for `<b>` as well as `</BOLD>`, `color` for `<fg=red>` and `<red>`.
+    pub fn name(&self) -> String {
+        canonical_tag_name(self.content)
+    }
+
+    /// The attributes of the tag as `(key, value)` pairs, with quoted values unescaped.
+    ///
+    /// ```rust
+    /// use richparse::events::{Event, Events};
+    ///
+    /// let Some(Event::Start(tag)) = Events::new("<color=red link=\"a b\" bold>").next() else { panic!() };
+    /// let attributes: Vec<_> = tag.attributes().collect();
+    /// assert_eq!(attributes[0], ("color", Some("red".into())));
+    /// assert_eq!(attributes[1], ("link", Some("a b".into())));
+    /// assert_eq!(attributes[2], ("bold", None));
+    /// ```
+    pub fn attributes(&self) -> impl Iterator<Item = (&'a str, Option<Cow<'a, str>>)> + use<'a> {
+        parse_attributes(self.content)
+            .into_iter()
+            .map(|attribute| (attribute.key, attribute.value))
+    }
+
+    /// Returns the style this tag applies on top of the default style.
+    ///
+    /// Errors are reported as in strict parsing, with spans in the input.
+    pub fn style(&self) -> Result<Style<'a>, ParseError> {
+        self.resolve(None)
+    }
+
+    /// Returns the style this tag applies, resolving custom tags through `registry` first.
+    pub fn style_with(&self, registry: &TagRegistry) -> Result<Style<'a>, ParseError> {
+        self.resolve(Some(registry))
+    }
+
+    fn resolve(&self, registry: Option<&TagRegistry>) -> Result<Style<'a>, ParseError> {
+        let mut style = Style::default();
+        apply_tag(self.content, self.offset, &mut style, registry).map_err(|err| err.into_parse_error(self.span()))?;
+        Ok(style)
+    }
+}
+
+/// An iterator over the [`Event`]s of markup.
+///
+/// Events follow the markup as written: closing tags are not matched against
+/// opening ones and tags are not validated, so every input produces events.
+/// Use [`Events::into_offset_iter`] to get the byte range of each event too.
+#[derive(Debug, Clone)]
+pub struct Events<'a> {
+    tokens: Tokens<'a>,
+    registry: Option<&'a TagRegistry>,
+}
+
+impl<'a> Events<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            tokens: Tokens::new(input),
+            registry: None,
+        }
+    }
+
+    /// Creates events for markup parsed with `registry`, as by
+    /// [`RichString::parse_with`](crate::RichString::parse_with): a registered
+    /// `br` or `hr` is a [`Event::Start`] tag rather than an [`Event::Empty`] one.
+    pub fn with_registry(input: &'a str, registry: &'a TagRegistry) -> Self {
+        Self {
+            tokens: Tokens::new(input),
+            registry: Some(registry),
+        }
+    }
+
+    /// Pairs each event with its byte range in the input.
+    ///
+    /// ```rust
+    /// use richparse::events::{Event, Events};
+    ///
+    /// let input = "a <<b <i>c";
+    /// let ranges: Vec<_> = Events::new(input).into_offset_iter().map(|(_, range)| &input[range]).collect();
+    /// assert_eq!(ranges, ["a ", "<<", "b ", "<i>", "c"]);
+    /// ```
+    pub fn into_offset_iter(self) -> OffsetEvents<'a> {
+        OffsetEvents {
+            tokens: self.tokens,
+            registry: self.registry,
+        }
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokens.next().map(|(token, span)| event(token, span, self.registry))
+    }
+}
+
+/// An iterator over the [`Event`]s of markup and their byte ranges; see [`Events::into_offset_iter`].
+#[derive(Debug, Clone)]
+pub struct OffsetEvents<'a> {
+    tokens: Tokens<'a>,
+    registry: Option<&'a TagRegistry>,
+}
+
+impl<'a> Iterator for OffsetEvents<'a> {
+    type Item = (Event<'a>, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokens.next().map(|(token, span)| (event(token, span.clone(), self.registry), span))
+    }
+}
+
+/// Converts a token found at `span` into an event, with `registry` deciding
+/// which inline elements are overridden.
+fn event<'a>(token: Token<'a>, span: Range<usize>, registry: Option<&TagRegistry>) -> Event<'a> {
+    match token {
+        Token::Text(text) => Event::Text(text),
+        Token::Escaped => Event::Escaped,
+        Token::Unterminated => Event::Unterminated,
+        Token::Close(content) => Event::End(Tag {
+            content,
+            offset: span.start + 2,
+            span,
+        }),
+        Token::Open(content) => {
+            let (content, self_closing) = split_self_closing(content);
+            let tag = Tag {
+                content,
+                offset: span.start + 1,
+                span,
+            };
+            if self_closing || inline_element(content, Style::default(), registry).is_some() {
+                Event::Empty(tag)
+            } else {
+                Event::Start(tag)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::Color;
+
+    #[test]
+    fn test_events() {
+        let input = "<red>a<<</red> <b/><hr/>x</>y <";
+        let events: Vec<_> = Events::new(input).into_offset_iter().collect();
+        let kinds: Vec<_> = events
+            .iter()
+            .map(|(event, range)| match event {
+                Event::Start(tag) => format!("start {} {:?}", tag.as_str(), range),
+                Event::End(tag) => format!("end {} {:?}", tag.as_str(), range),
+                Event::Empty(tag) => format!("empty {} {:?}", tag.as_str(), range),
+                Event::Text(text) => format!("text {} {:?}", text, range),
+                Event::Escaped => format!("escaped {:?}", range),
+                Event::Unterminated => format!("unterminated {:?}", range),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "start red 0..5",
+                "text a 5..6",
+                "escaped 6..8",
+                "end red 8..14",
+                "text   14..15",
+                "empty b 15..19",
+                "empty hr 19..24",
+                "text x 24..25",
+                "end  25..28",
+                "text y  28..30",
+                "unterminated 30..31",
+            ]
+        );
+        // The ranges cover the input without gaps
+        let covered: String = events.iter().map(|(_, range)| &input[range.clone()]).collect();
+        assert_eq!(covered, input);
+    }
+
+    #[test]
+    fn test_tag_style() {
+        let input = "x <fg=bright red U>y</fg>";
+        let Some(Event::Start(tag)) = Events::new(input).nth(1) else {
+            panic!("expected a start tag");
+        };
+        assert_eq!(tag.name(), "color");
+        assert_eq!(tag.span(), 2..19);
+        assert_eq!(tag.style().unwrap(), Style::new().fg(Color::BrightRed).underline());
+
+        let Some(Event::Start(tag)) = Events::new("a <color=nope>").nth(1) else {
+            panic!("expected a start tag");
+        };
+        assert_eq!(tag.style().unwrap_err(), ParseError::InvalidColor { value: "nope".to_string(), span: 9..13 });
+
+        let mut registry = TagRegistry::new();
+        registry.register("warn", Style::new().fg(Color::Yellow));
+        let Some(Event::Start(tag)) = Events::new("<warn>").next() else {
+            panic!("expected a start tag");
+        };
+        assert!(tag.style().is_err());
+        assert_eq!(tag.style_with(&registry).unwrap().fg, Color::Yellow);
+    }
+
+    #[test]
+    fn test_events_with_registry() {
+        let mut registry = TagRegistry::new();
+        registry.register("hr", Style::new().italic());
+        let input = "<hr>x</hr><br>";
+        assert!(matches!(Events::new(input).next(), Some(Event::Empty(_))));
+        let events: Vec<_> = Events::with_registry(input, &registry).into_offset_iter().collect();
+        assert!(matches!(events[0], (Event::Start(ref tag), ref range) if tag.name() == "hr" && *range == (0..4)));
+        assert!(matches!(events[3].0, Event::Empty(ref tag) if tag.name() == "br"));
+    }
+}
@@ -0,0 +1,185 @@
+//! Support for [`rich_format!`](crate::rich_format) and the macros built on it.
+//!
+//! The template is formatted with [`format_args!`] into a `MarkupWriter`.
+//! Each argument is wrapped so that its output is escaped while it is being
+//! written, which leaves the template itself as the only markup. Arguments
+//! that are already a [`RichString`] are written as markup instead, so they
+//! keep their styles. The choice is made with autoref specialization: the
+//! macro calls `(&Wrap(&value)).__rich_arg(..)`, which finds [`RichArg`] for
+//! rich strings before it tries [`TextArg`] through a second reference.
+
+use std::cell::Cell;
+use std::fmt::{self, Display, Write};
+use crate::escape::escape;
+use crate::parser::RichString;
+use crate::sanitize::sanitize;
+
+/// Collects the markup written by [`format_args!`], sanitizing and escaping the
+/// output of arguments while `escaping` is set.
+struct MarkupWriter<'e> {
+    markup: String,
+    escaping: &'e Cell<bool>,
+}
+
+impl Write for MarkupWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.escaping.get() {
+            self.markup.push_str(&escape(&sanitize(s)));
+        } else {
+            self.markup.push_str(s);
+        }
+        Ok(())
+    }
+}
+
+/// Formats `args` as markup and parses it.
+pub fn format(escaping: &Cell<bool>, args: fmt::Arguments<'_>) -> RichString<'static> {
+    let mut writer = MarkupWriter {
+        markup: String::new(),
+        escaping,
+    };
+    // Writing to a String cannot fail, and neither do the arguments' own impls
+    let _ = writer.write_fmt(args);
+    RichString::parse(&writer.markup).into_owned()
+}
+
+/// A `rich_format!` argument before it is known to be a rich string or plain text.
+pub struct Wrap<'v, T>(pub &'v T);
+
+/// A formatted argument of `rich_format!`.
+pub struct Arg<'v> {
+    value: Value<'v>,
+    escaping: &'v Cell<bool>,
+}
+
+enum Value<'v> {
+    Rich(&'v RichString<'v>),
+    Text(&'v dyn Display),
+}
+
+/// Splices a [`RichString`] argument in with its own styles.
+pub trait RichArg<'v> {
+    fn __rich_arg(&self, escaping: &'v Cell<bool>) -> Arg<'v>;
+}
+
+impl<'v, 'a: 'v> RichArg<'v> for Wrap<'v, RichString<'a>> {
+    fn __rich_arg(&self, escaping: &'v Cell<bool>) -> Arg<'v> {
+        Arg {
+            value: Value::Rich(self.0),
+            escaping,
+        }
+    }
+}
+
+impl<'v, 'a: 'v> RichArg<'v> for Wrap<'v, &'v RichString<'a>> {
+    fn __rich_arg(&self, escaping: &'v Cell<bool>) -> Arg<'v> {
+        Arg {
+            value: Value::Rich(self.0),
+            escaping,
+        }
+    }
+}
+
+/// Inserts any other argument as literal text, using its `Display` impl.
+pub trait TextArg<'v> {
+    fn __rich_arg(&self, escaping: &'v Cell<bool>) -> Arg<'v>;
+}
+
+impl<'v, T: Display> TextArg<'v> for &Wrap<'v, T> {
+    fn __rich_arg(&self, escaping: &'v Cell<bool>) -> Arg<'v> {
+        Arg {
+            value: Value::Text(self.0),
+            escaping,
+        }
+    }
+}
+
+impl Display for Arg<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            Value::Text(value) => {
+                self.escaping.set(true);
+                let result = value.fmt(f);
+                self.escaping.set(false);
+                result
+            }
+            Value::Rich(rich) => {
+                let (text, before, after) = rich.layout(f);
+                self.escaping.set(true);
+                let fill = f.fill();
+                for _ in 0..before {
+                    write!(f, "{}", fill)?;
+                }
+                self.escaping.set(false);
+                f.write_str(&text.to_markup())?;
+                self.escaping.set(true);
+                for _ in 0..after {
+                    write!(f, "{}", fill)?;
+                }
+                self.escaping.set(false);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Returns `true` if every placeholder in `template` refers to an argument by
+/// position or to one of `named`.
+///
+/// Arguments captured implicitly, as in `{name}`, bypass the wrapping that
+/// escapes them, so `rich_format!` rejects them at compile time.
+pub const fn explicit_arguments(template: &str, named: &[&str]) -> bool {
+    let bytes = template.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'{' && i + 1 < bytes.len() && bytes[i + 1] == b'{' {
+            i += 2;
+            continue;
+        }
+        if bytes[i] == b'{' {
+            let start = i + 1;
+            let mut end = start;
+            while end < bytes.len() && bytes[end] != b'}' && bytes[end] != b':' {
+                end += 1;
+            }
+            if end > start && !bytes[start].is_ascii_digit() && !is_named(bytes.split_at(end).0.split_at(start).1, named) {
+                return false;
+            }
+            i = end;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Returns `true` if `name` is one of `named`.
+const fn is_named(name: &[u8], named: &[&str]) -> bool {
+    let mut n = 0;
+    while n < named.len() {
+        let candidate = named[n].as_bytes();
+        if candidate.len() == name.len() {
+            let mut k = 0;
+            while k < name.len() && candidate[k] == name[k] {
+                k += 1;
+            }
+            if k == name.len() {
+                return true;
+            }
+        }
+        n += 1;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explicit_arguments() {
+        assert!(explicit_arguments("<red>{}</red> {0} {:>5} {{name}}", &[]));
+        assert!(explicit_arguments("{name} {name:?} {:width$}", &["name"]));
+        assert!(!explicit_arguments("{name}", &[]));
+        assert!(!explicit_arguments("{} {other}", &["name"]));
+    }
+}
@@ -0,0 +1,430 @@
+use std::fmt;
+use crate::palette::Palette;
+use crate::sanitize::is_safe_url;
+use crate::span::Span;
+use crate::style::{Color, Style};
+
+/// How [`HtmlRenderer`] attaches styles to the HTML it writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HtmlMode {
+    /// Every style is written as a `style="..."` attribute.
+    #[default]
+    Inline,
+    /// Modifiers and the 16 basic colors are written as class names (see
+    /// [`HtmlOptions::stylesheet`]); 256-color, RGB and underline colors stay inline.
+    Classes,
+}
+
+/// Options controlling how a [`RichString`](crate::RichString) is exported to HTML.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlOptions {
+    pub mode: HtmlMode,
+    /// Prepended to every class name in [`HtmlMode::Classes`].
+    pub class_prefix: String,
+    pub palette: Palette,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        Self {
+            mode: HtmlMode::default(),
+            class_prefix: "rp-".to_string(),
+            palette: Palette::default(),
+        }
+    }
+}
+
+impl HtmlOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mode(mut self, mode: HtmlMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn class_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.class_prefix = prefix.into();
+        self
+    }
+
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Returns the CSS rules for the class names written in [`HtmlMode::Classes`].
+    pub fn stylesheet(&self) -> String {
+        let p = &self.class_prefix;
+        let mut css = String::new();
+        for (i, name) in COLOR_NAMES.iter().enumerate() {
+            let hex = hex(self.palette.colors[i]);
+            css.push_str(&format!(".{p}fg-{name} {{ color: {hex}; }}\n"));
+            css.push_str(&format!(".{p}bg-{name} {{ background-color: {hex}; }}\n"));
+        }
+        let rules = [
+            ("bold", "font-weight: bold"),
+            ("dim", "opacity: 0.5"),
+            ("italic", "font-style: italic"),
+            ("hidden", "visibility: hidden"),
+            ("underline", "text-decoration-line: underline"),
+            ("overline", "text-decoration-line: overline"),
+            ("strikethrough", "text-decoration-line: line-through"),
+            ("double", "text-decoration-style: double"),
+            ("curly", "text-decoration-style: wavy"),
+        ];
+        for (class, rule) in rules {
+            css.push_str(&format!(".{p}{class} {{ {rule}; }}\n"));
+        }
+        // Lines combine, so every combination needs its own rule
+        let combinations = [
+            ("underline", "overline", None),
+            ("underline", "strikethrough", None),
+            ("overline", "strikethrough", None),
+            ("underline", "overline", Some("strikethrough")),
+        ];
+        for (a, b, c) in combinations {
+            let mut selector = format!(".{p}{a}.{p}{b}");
+            let mut lines: Vec<&str> = vec![decoration_line(a), decoration_line(b)];
+            if let Some(c) = c {
+                selector.push_str(&format!(".{p}{c}"));
+                lines.push(decoration_line(c));
+            }
+            css.push_str(&format!("{selector} {{ text-decoration-line: {}; }}\n", lines.join(" ")));
+        }
+        css
+    }
+}
+
+const COLOR_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "bright-black",
+    "bright-red",
+    "bright-green",
+    "bright-yellow",
+    "bright-blue",
+    "bright-magenta",
+    "bright-cyan",
+    "bright-white",
+];
+
+fn decoration_line(class: &str) -> &'static str {
+    match class {
+        "underline" => "underline",
+        "overline" => "overline",
+        _ => "line-through",
+    }
+}
+
+/// Writes spans as HTML, one `<span>` per styled span, grouping consecutive
+/// spans with the same link into a single `<a href>`.
+///
+/// The output contains no wrapping element; put it inside a `<pre>` to keep
+/// whitespace and line breaks.
+///
+/// ```rust
+/// use richparse::RichString;
+/// use richparse::html::HtmlOptions;
+///
+/// let rs = RichString::parse("<bold>1 << 2</bold> & <link=https://example.com>docs</link>");
+/// assert_eq!(
+///     rs.to_html(&HtmlOptions::new()),
+///     "<span style=\"font-weight: bold\">1 &lt; 2</span> &amp; <a href=\"https://example.com\">docs</a>"
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HtmlRenderer {
+    options: HtmlOptions,
+    link: Option<String>,
+    /// The attributes of the `<span>` being written, if any text has been written since
+    /// the last one was closed; an empty string for unstyled text.
+    open: Option<String>,
+}
+
+impl HtmlRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: HtmlOptions) -> Self {
+        Self {
+            options,
+            link: None,
+            open: None,
+        }
+    }
+
+    /// Writes `span`, opening or closing a link if its URL differs from the previous
+    /// span's. Adjacent spans with the same style share a single `<span>`.
+    ///
+    /// Only `http`, `https`, `mailto` and `file` URLs become links.
+    pub fn write_span<W: fmt::Write>(&mut self, out: &mut W, span: &Span<'_>) -> fmt::Result {
+        if span.text.is_empty() && !span.is_rule() {
+            return Ok(());
+        }
+
+        let attributes = self.attributes(&span.style);
+        // Links with other schemes, such as `javascript:`, are written as plain text
+        let url = span.style.url.as_deref().filter(|url| is_safe_url(url));
+        let link_changed = url != self.link.as_deref();
+        if (link_changed || self.open.as_ref() != Some(&attributes))
+            && self.open.take().is_some_and(|open| !open.is_empty())
+        {
+            out.write_str("</span>")?;
+        }
+
+        if link_changed {
+            if self.link.is_some() {
+                out.write_str("</a>")?;
+            }
+            if let Some(url) = url {
+                out.write_str("<a href=\"")?;
+                write_escaped(out, url)?;
+                out.write_str("\">")?;
+            }
+            self.link = url.map(str::to_string);
+        }
+
+        if span.is_rule() {
+            // A rule is a block of its own and is never merged with text
+            if self.open.take().is_some_and(|open| !open.is_empty()) {
+                out.write_str("</span>")?;
+            }
+            return write!(out, "<hr{}>", attributes);
+        }
+
+        if self.open.is_none() {
+            if !attributes.is_empty() {
+                write!(out, "<span{}>", attributes)?;
+            }
+            self.open = Some(attributes);
+        }
+        write_escaped(out, &span.text)
+    }
+
+    /// Closes any open `<span>` and link.
+    pub fn finish<W: fmt::Write>(&mut self, out: &mut W) -> fmt::Result {
+        if self.open.take().is_some_and(|open| !open.is_empty()) {
+            out.write_str("</span>")?;
+        }
+        if self.link.take().is_some() {
+            out.write_str("</a>")?;
+        }
+        Ok(())
+    }
+
+    /// Returns the `class` and `style` attributes for `style`, each preceded by a space.
+    fn attributes(&self, style: &Style<'_>) -> String {
+        let (classes, css) = self.classes_and_css(style);
+        let mut attributes = String::new();
+        if !classes.is_empty() {
+            attributes.push_str(&format!(" class=\"{}\"", classes.join(" ")));
+        }
+        if !css.is_empty() {
+            attributes.push_str(&format!(" style=\"{}\"", css.join("; ")));
+        }
+        attributes
+    }
+
+    /// Returns the class names and CSS declarations for `style`.
+    fn classes_and_css(&self, style: &Style<'_>) -> (Vec<String>, Vec<String>) {
+        let palette = &self.options.palette;
+        let classes_mode = self.options.mode == HtmlMode::Classes;
+        let prefix = &self.options.class_prefix;
+        let mut classes = Vec::new();
+        let mut css = Vec::new();
+
+        let (mut fg, mut bg) = (style.fg, style.bg);
+        if style.inverse {
+            let resolve = |color: Color, default: (u8, u8, u8)| match color {
+                Color::Default => {
+                    let (r, g, b) = default;
+                    Color::Rgb(r, g, b)
+                }
+                color => color,
+            };
+            (fg, bg) = (resolve(bg, palette.background), resolve(fg, palette.foreground));
+        }
+
+        for (color, class, property) in [(fg, "fg", "color"), (bg, "bg", "background-color")] {
+            match color.ansi16_index() {
+                Some(i) if classes_mode => classes.push(format!("{prefix}{class}-{}", COLOR_NAMES[i as usize])),
+                _ => {
+                    if let Some(rgb) = palette.rgb(color) {
+                        css.push(format!("{property}: {}", hex(rgb)));
+                    }
+                }
+            }
+        }
+
+        let underline = style.underline || style.double_underline || style.curly_underline;
+        let underline_style = if style.curly_underline {
+            Some("curly")
+        } else if style.double_underline {
+            Some("double")
+        } else {
+            None
+        };
+
+        if classes_mode {
+            let flags = [
+                (style.bold, "bold"),
+                (style.dim, "dim"),
+                (style.italic, "italic"),
+                (style.hidden, "hidden"),
+                (underline, "underline"),
+                (style.overline, "overline"),
+                (style.strikethrough, "strikethrough"),
+            ];
+            for (enabled, class) in flags {
+                if enabled {
+                    classes.push(format!("{prefix}{class}"));
+                }
+            }
+            if let Some(class) = underline_style {
+                classes.push(format!("{prefix}{class}"));
+            }
+        } else {
+            if style.bold {
+                css.push("font-weight: bold".to_string());
+            }
+            if style.dim {
+                css.push("opacity: 0.5".to_string());
+            }
+            if style.italic {
+                css.push("font-style: italic".to_string());
+            }
+            if style.hidden {
+                css.push("visibility: hidden".to_string());
+            }
+            let lines: Vec<&str> = [
+                (underline, "underline"),
+                (style.overline, "overline"),
+                (style.strikethrough, "line-through"),
+            ]
+            .into_iter()
+            .filter_map(|(enabled, line)| enabled.then_some(line))
+            .collect();
+            if !lines.is_empty() {
+                css.push(format!("text-decoration-line: {}", lines.join(" ")));
+            }
+            match underline_style {
+                Some("curly") => css.push("text-decoration-style: wavy".to_string()),
+                Some(_) => css.push("text-decoration-style: double".to_string()),
+                None => {}
+            }
+        }
+
+        if underline && let Some(rgb) = style.underline_color.and_then(|color| palette.rgb(color)) {
+            css.push(format!("text-decoration-color: {}", hex(rgb)));
+        }
+
+        (classes, css)
+    }
+}
+
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Writes `text` with the characters that are special in HTML text and attributes escaped.
+fn write_escaped<W: fmt::Write>(out: &mut W, text: &str) -> fmt::Result {
+    let mut last = 0;
+    for (i, c) in text.char_indices() {
+        let escaped = match c {
+            '&' => "&amp;",
+            '<' => "&lt;",
+            '>' => "&gt;",
+            '"' => "&quot;",
+            '\'' => "&#39;",
+            _ => continue,
+        };
+        out.write_str(&text[last..i])?;
+        out.write_str(escaped)?;
+        last = i + c.len_utf8();
+    }
+    out.write_str(&text[last..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::RichString;
+
+    #[test]
+    fn test_html_inline() {
+        let rs = RichString::parse("<style=\"red on #102030\"><i>a</i></style>");
+        assert_eq!(
+            rs.to_html(&HtmlOptions::new()),
+            "<span style=\"color: #cd0000; background-color: #102030; font-style: italic\">a</span>"
+        );
+    }
+
+    #[test]
+    fn test_html_text_decoration() {
+        let rs = RichString::parse("<cu=red><o>a</o></cu>");
+        assert_eq!(
+            rs.to_html(&HtmlOptions::new()),
+            "<span style=\"text-decoration-line: underline overline; text-decoration-style: wavy; text-decoration-color: #cd0000\">a</span>"
+        );
+        let rs = RichString::parse("<uu><s>b</s></uu>");
+        assert_eq!(
+            rs.to_html(&HtmlOptions::new()),
+            "<span style=\"text-decoration-line: underline line-through; text-decoration-style: double\">b</span>"
+        );
+    }
+
+    #[test]
+    fn test_html_classes() {
+        let rs = RichString::parse("<color=bright_green><b><uu>a</uu></b></color><color=208>b</color>");
+        let options = HtmlOptions::new().mode(HtmlMode::Classes);
+        assert_eq!(
+            rs.to_html(&options),
+            "<span class=\"rp-fg-bright-green rp-bold rp-underline rp-double\">a</span><span style=\"color: #ff8700\">b</span>"
+        );
+        let css = options.stylesheet();
+        assert!(css.contains(".rp-fg-bright-green { color: #00ff00; }"));
+        assert!(css.contains(".rp-underline.rp-overline { text-decoration-line: underline overline; }"));
+    }
+
+    #[test]
+    fn test_html_inverse() {
+        let rs = RichString::parse("<inverse><red>a</red></inverse>");
+        assert_eq!(
+            rs.to_html(&HtmlOptions::new()),
+            "<span style=\"color: #000000; background-color: #cd0000\">a</span>"
+        );
+    }
+
+    #[test]
+    fn test_html_links_and_escaping() {
+        let rs = RichString::parse("<link=\"https://x.dev/?a=1&b='2'\">a <b>b</b></link> <<c>");
+        assert_eq!(
+            rs.to_html(&HtmlOptions::new()),
+            "<a href=\"https://x.dev/?a=1&amp;b=&#39;2&#39;\">a <span style=\"font-weight: bold\">b</span></a> &lt;c&gt;"
+        );
+    }
+
+    #[test]
+    fn test_html_unsafe_links() {
+        let rs = RichString::parse("<link=\"javascript:alert(1)\">click</link> <link=\"data:text/html,x\">me</link>");
+        assert_eq!(rs.to_html(&HtmlOptions::new()), "click me");
+    }
+
+    #[test]
+    fn test_html_rule() {
+        let rs = RichString::parse("<b>a<hr/>b</b><hr>");
+        assert_eq!(
+            rs.to_html(&HtmlOptions::new()),
+            "<span style=\"font-weight: bold\">a</span><hr style=\"font-weight: bold\"><span style=\"font-weight: bold\">b</span><hr>"
+        );
+    }
+}
@@ -17,10 +17,37 @@
 pub mod style;
 pub mod span;
 pub mod parser;
+pub mod error;
+pub mod registry;
+pub mod theme;
+pub mod render;
+pub mod html;
+pub mod svg;
+pub mod wrap;
+pub mod events;
+mod ansi;
+mod markup;
+mod width;
+mod truncate;
+mod palette;
+mod format;
+mod escape;
+mod sanitize;
+mod stream;
 
 pub use style::{Color, Style};
 pub use span::Span;
-pub use parser::RichString;
+pub use parser::{ClosePolicy, ParseOptions, RichString};
+pub use error::{ParseError, ThemeError};
+pub use registry::TagRegistry;
+pub use theme::Theme;
+pub use palette::Palette;
+pub use wrap::WrapOptions;
+pub use truncate::Ellipsis;
+pub use escape::{escape, unescape, Escaped};
+pub use sanitize::sanitize;
+pub use stream::Parser;
+pub use render::{AnsiRenderer, ColorDepth, PlainText, RenderOptions};
 
 #[macro_export]
 macro_rules! rich {
@@ -29,6 +56,111 @@ macro_rules! rich {
     };
 }
 
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::format::{explicit_arguments, format, RichArg, TextArg, Wrap};
+}
+
+/// Formats arguments into a [`RichString`] like [`format!`], with the
+/// template parsed as markup and the arguments inserted as literal text.
+///
+/// An argument can never open or close a tag, whatever it contains, so
+/// filenames and error messages are safe to interpolate. Escape sequences and
+/// control characters in arguments are neutralized as by [`sanitize`], so
+/// they cannot take over the terminal either. Arguments that are
+/// themselves a `RichString` are spliced in with their own styles, on top of
+/// the style around the placeholder. Width, fill, alignment and precision
+/// work as usual; arguments are formatted with `Display`, so for `{:?}` pass
+/// `format!("{:?}", value)` instead.
+///
+/// Arguments must be passed explicitly; `{name}` without `name = ...` is a
+/// compile error, since an implicitly captured argument could not be escaped.
+///
+/// ```rust
+/// use richparse::{rich, rich_format, Color};
+///
+/// let file = "<notes>.txt";
+/// let rs = rich_format!("<red>cannot open {}</red>: {}", file, rich!("<b>denied</b>"));
+/// assert_eq!(rs.plain().to_string(), "cannot open <notes>.txt: denied");
+/// assert_eq!(rs.spans[0].style.fg, Color::Red);
+/// ```
+///
+/// ```compile_fail
+/// let file = "<notes>.txt";
+/// let rs = richparse::rich_format!("cannot open {file}");
+/// ```
+#[macro_export]
+macro_rules! rich_format {
+    ($template:literal $(,)?) => {
+        $crate::__rich_format!(@args $template [] [])
+    };
+    ($template:literal, $($args:tt)+) => {
+        $crate::__rich_format!(@args $template [] [] $($args)+)
+    };
+}
+
+/// Sorts the arguments of [`rich_format!`] into positional and named ones.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __rich_format {
+    (@args $template:literal [$($pos:expr,)*] [$($name:ident = $named:expr,)*] $key:ident = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__rich_format!(@args $template [$($pos,)*] [$($name = $named,)* $key = $value,] $($($rest)*)?)
+    };
+    (@args $template:literal [$($pos:expr,)*] [$($name:ident = $named:expr,)*] $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__rich_format!(@args $template [$($pos,)* $value,] [$($name = $named,)*] $($($rest)*)?)
+    };
+    (@args $template:literal [$($pos:expr,)*] [$($name:ident = $named:expr,)*]) => {{
+        #[allow(unused_imports)]
+        use $crate::__private::{RichArg as _, TextArg as _};
+        const {
+            assert!(
+                $crate::__private::explicit_arguments($template, &[$(stringify!($name)),*]),
+                "rich_format! arguments must be passed explicitly, e.g. `{{name}}` with `name = name`"
+            )
+        };
+        let escaping = ::std::cell::Cell::new(false);
+        $crate::__private::format(
+            &escaping,
+            ::std::format_args!(
+                $template,
+                $((&$crate::__private::Wrap(&$pos)).__rich_arg(&escaping),)*
+                $($name = (&$crate::__private::Wrap(&$named)).__rich_arg(&escaping),)*
+            ),
+        )
+    }};
+}
+
+/// Prints markup to stdout with a newline, escaping the arguments; see [`rich_format!`].
+#[macro_export]
+macro_rules! rich_println {
+    () => {
+        ::std::println!()
+    };
+    ($($args:tt)+) => {
+        ::std::println!("{}", $crate::rich_format!($($args)+))
+    };
+}
+
+/// Prints markup to stderr with a newline, escaping the arguments; see [`rich_format!`].
+#[macro_export]
+macro_rules! rich_eprintln {
+    () => {
+        ::std::eprintln!()
+    };
+    ($($args:tt)+) => {
+        ::std::eprintln!("{}", $crate::rich_format!($($args)+))
+    };
+}
+
+/// Writes markup to a [`std::fmt::Write`] or [`std::io::Write`] destination,
+/// escaping the arguments; see [`rich_format!`].
+#[macro_export]
+macro_rules! rich_write {
+    ($dst:expr, $($args:tt)+) => {
+        ::std::write!($dst, "{}", $crate::rich_format!($($args)+))
+    };
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -40,7 +172,54 @@ mod tests {
         let rich_string = RichString::new(vec![span]);
         
 
-        let expected = "\x1b[31m\x1b[49m\x1b[1mHello\x1b[0m";
+        let expected = "\x1b[31;1mHello\x1b[0m";
         assert_eq!(rich_string.to_string(), expected);
+
+        // A span on its own is rendered in full
+        let expected = "\x1b[31m\x1b[49m\x1b[1mHello\x1b[0m";
+        assert_eq!(rich_string.spans[0].to_string(), expected);
+    }
+
+    #[test]
+    fn test_rich_format_escapes_arguments() {
+        let input = "</red><b>x<hr/>";
+        let rs = rich_format!("<red>{}</red>", input);
+        assert_eq!(rs.normalize().spans, vec![Span::new("</red><b>x<hr/>", Style::new().fg(Color::Red))]);
+
+        let rs = rich_format!("{:<<6}|{0}|{name:>4}", "<a", name = 1);
+        assert_eq!(rs.plain().to_string(), "<a<<<<|<a|   1");
+
+        let title = "x\x1b]0;pwned\x07\ry";
+        assert_eq!(rich_format!("<b>{}</b>", title).plain().to_string(), "x␍y");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_rich_format_splices_rich_strings() {
+        let label = rich!("<b>ok</b> <<3");
+        let rs = rich_format!("<red>[{:^8}]</red> {}", label, &label);
+        assert_eq!(rs.plain().to_string(), "[ ok <3  ] ok <3");
+        assert_eq!(rs.spans[1], Span::new("ok", Style::new().fg(Color::Red).bold()));
+        assert_eq!(rs.spans.last().unwrap().style, Style::default());
+        assert_eq!(rich_format!("{:.2}", label).plain().to_string(), "ok");
+    }
+
+    #[test]
+    fn test_rich_write() {
+        use std::fmt::Write;
+        let mut out = String::new();
+        rich_write!(out, "<b>{}</b>", "<x>").unwrap();
+        assert_eq!(out, "\x1b[1m<x>\x1b[0m");
+    }
+
+    #[test]
+    fn test_rule_text_is_plain() {
+        let span = Span::new("\u{fdd0}", Style::default());
+        assert!(!span.is_rule());
+        assert_eq!(span.width(), 1);
+
+        let rs = rich_format!("<b>{}</b>", "\u{fdd0}");
+        assert!(rs.spans.iter().all(|span| !span.is_rule()));
+        assert_eq!(rs.plain().to_string(), "\u{fdd0}");
+        assert_eq!(rs.width(), 1);
+    }
+}
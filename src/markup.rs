@@ -0,0 +1,309 @@
+//! Serializing a [`RichString`] back to markup.
+
+use crate::escape::escape;
+use crate::parser::RichString;
+use crate::style::{Color, Style};
+
+/// An open tag while writing markup: the key it is closed by and the style in effect inside it.
+struct OpenTag {
+    name: String,
+    style: Style<'static>,
+}
+
+/// Writes `rich` as markup that parses back to the same spans, up to [`RichString::normalize`].
+///
+/// Tags stay open while the spans inside them keep their attributes, so
+/// `<b>a<red>b</red></b>` rather than `<b>a</b><b red>b</b>`. A tag whose
+/// attributes no longer all apply is closed, together with the tags opened
+/// after it. The remaining difference is opened as a new tag, split in two
+/// when the next span shares some of it.
+pub(crate) fn to_markup(rich: &RichString<'_>) -> String {
+    let mut out = String::new();
+    let mut stack: Vec<OpenTag> = Vec::new();
+
+    let spans: Vec<_> = rich.spans.iter().filter(|span| !span.text.is_empty() || span.is_rule()).collect();
+    for (i, span) in spans.iter().enumerate() {
+        let target = &span.style;
+
+        // Keep the longest run of open tags whose attributes all still apply
+        let keep = stack
+            .iter()
+            .take_while(|tag| is_within(&tag.style, target))
+            .count();
+        for tag in stack.drain(keep..).rev() {
+            out.push_str(&format!("</{}>", tag.name));
+        }
+
+        let current = stack.last().map(|tag| tag.style.clone()).unwrap_or_default();
+        if current != *target {
+            // Open what the next span shares in an outer tag, so it can stay open
+            let shared = spans.get(i + 1).map(|next| shared(&current, target, &next.style));
+            if let Some(shared) = shared
+                && shared != current
+                && shared != *target
+            {
+                open(&mut out, &mut stack, &current, &shared);
+                open(&mut out, &mut stack, &shared, target);
+            } else {
+                open(&mut out, &mut stack, &current, target);
+            }
+        }
+
+        if span.is_rule() {
+            out.push_str("<hr/>");
+        } else {
+            out.push_str(&escape(&span.text));
+        }
+    }
+
+    for tag in stack.iter().rev() {
+        out.push_str(&format!("</{}>", tag.name));
+    }
+    out
+}
+
+/// Writes a tag turning `from` into `to` and pushes it onto `stack`.
+fn open(out: &mut String, stack: &mut Vec<OpenTag>, from: &Style<'_>, to: &Style<'_>) {
+    let attributes = attributes(from, to);
+    out.push_str(&format!("<{}>", attributes.join(" ")));
+    stack.push(OpenTag {
+        name: attributes[0].split('=').next().unwrap_or_default().to_string(),
+        style: to.clone().into_owned(),
+    });
+}
+
+/// Returns `current` with the attributes that `target` adds to it and `next` also has.
+fn shared(current: &Style<'_>, target: &Style<'_>, next: &Style<'_>) -> Style<'static> {
+    let mut shared = current.clone().into_owned();
+    if target.fg != current.fg && next.fg == target.fg {
+        shared.fg = target.fg;
+    }
+    if target.bg != current.bg && next.bg == target.bg {
+        shared.bg = target.bg;
+    }
+    if target.underline_color != current.underline_color && next.underline_color == target.underline_color {
+        shared.underline_color = target.underline_color;
+    }
+    if target.url != current.url && next.url == target.url {
+        shared.url = target.url.clone().map(|url| url.into_owned().into());
+    }
+    let flags = [
+        (target.bold && next.bold, &mut shared.bold),
+        (target.dim && next.dim, &mut shared.dim),
+        (target.italic && next.italic, &mut shared.italic),
+        (target.underline && next.underline, &mut shared.underline),
+        (target.double_underline && next.double_underline, &mut shared.double_underline),
+        (target.curly_underline && next.curly_underline, &mut shared.curly_underline),
+        (target.overline && next.overline, &mut shared.overline),
+        (target.strikethrough && next.strikethrough, &mut shared.strikethrough),
+        (target.blink && next.blink, &mut shared.blink),
+        (target.inverse && next.inverse, &mut shared.inverse),
+        (target.hidden && next.hidden, &mut shared.hidden),
+    ];
+    for (both, flag) in flags {
+        *flag |= both;
+    }
+    shared
+}
+
+/// Returns `true` if every attribute set in `outer` has the same value in `inner`,
+/// so text styled `inner` can stay inside a tag that produced `outer`.
+fn is_within(outer: &Style<'_>, inner: &Style<'_>) -> bool {
+    let default = Style::default();
+    let same_or_unset = |outer_differs: bool, equal: bool| !outer_differs || equal;
+    same_or_unset(outer.fg != default.fg, outer.fg == inner.fg)
+        && same_or_unset(outer.bg != default.bg, outer.bg == inner.bg)
+        && same_or_unset(outer.underline_color.is_some(), outer.underline_color == inner.underline_color)
+        && same_or_unset(outer.url.is_some(), outer.url == inner.url)
+        && flags(outer).iter().zip(flags(inner)).all(|((set, _), (still_set, _))| !set || still_set)
+}
+
+/// The modifiers of `style`, paired with the shortest tag that sets each one.
+fn flags(style: &Style<'_>) -> [(bool, &'static str); 11] {
+    [
+        (style.bold, "b"),
+        (style.dim, "dim"),
+        (style.italic, "i"),
+        (style.underline, "u"),
+        (style.double_underline, "uu"),
+        (style.curly_underline, "cu"),
+        (style.overline, "o"),
+        (style.strikethrough, "s"),
+        (style.blink, "blink"),
+        (style.inverse, "inverse"),
+        (style.hidden, "hidden"),
+    ]
+}
+
+/// Returns the attributes of a tag that turns `from` into `to`, where `from` is within `to`.
+fn attributes(from: &Style<'_>, to: &Style<'_>) -> Vec<String> {
+    let mut attributes = Vec::new();
+
+    if to.fg != from.fg {
+        attributes.push(match color_name(to.fg) {
+            Some(name) if !name.starts_with("bright") => name.to_string(),
+            _ => format!("color={}", color_value(to.fg)),
+        });
+    }
+    if to.bg != from.bg {
+        attributes.push(match color_name(to.bg) {
+            Some(name) if !name.starts_with("bright") => format!("bg_{}", name),
+            _ => format!("bg={}", color_value(to.bg)),
+        });
+    }
+
+    // An underline color is written together with the underline it colors when possible
+    let mut underline_color = (to.underline_color != from.underline_color)
+        .then_some(to.underline_color)
+        .flatten();
+    for ((was, _), (is, tag)) in flags(from).into_iter().zip(flags(to)) {
+        if is && !was {
+            match underline_color {
+                Some(color) if matches!(tag, "u" | "uu" | "cu") => {
+                    attributes.push(format!("{}={}", tag, color_value(color)));
+                    underline_color = None;
+                }
+                _ => attributes.push(tag.to_string()),
+            }
+        }
+    }
+    if let Some(color) = underline_color {
+        attributes.push(format!("underline_color={}", color_value(color)));
+    }
+
+    // Last, so a value such as `bright` can never absorb a following word
+    if to.url != from.url
+        && let Some(url) = &to.url
+    {
+        attributes.push(format!("link={}", quote(url)));
+    }
+
+    attributes
+}
+
+/// The name of a basic color, e.g. `red` or `bright_red`.
+fn color_name(color: Color) -> Option<&'static str> {
+    const NAMES: [&str; 16] = [
+        "black",
+        "red",
+        "green",
+        "yellow",
+        "blue",
+        "magenta",
+        "cyan",
+        "white",
+        "bright_black",
+        "bright_red",
+        "bright_green",
+        "bright_yellow",
+        "bright_blue",
+        "bright_magenta",
+        "bright_cyan",
+        "bright_white",
+    ];
+    color.ansi16_index().map(|i| NAMES[i as usize])
+}
+
+/// A color as written in an attribute value, e.g. `bright_red`, `208` or `#ff8800`.
+fn color_value(color: Color) -> String {
+    match color {
+        Color::Ansi256(n) => n.to_string(),
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        color => color_name(color).unwrap_or("default").to_string(),
+    }
+}
+
+/// Quotes an attribute value if it would otherwise end early or be misread.
+fn quote(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value.starts_with(['"', '\''])
+        || value.contains(|c: char| c.is_whitespace() || matches!(c, '>' | '"' | '\'' | '\\'));
+    if !needs_quotes {
+        return value.to_string();
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+
+    fn round_trip(markup: &str) -> String {
+        let rich = RichString::parse(markup);
+        let written = rich.to_markup();
+        assert_eq!(
+            RichString::try_parse(&written).unwrap().normalize(),
+            rich.clone().normalize(),
+            "{} -> {}",
+            markup,
+            written
+        );
+        written
+    }
+
+    #[test]
+    fn test_to_markup_nesting() {
+        assert_eq!(round_trip("plain"), "plain");
+        assert_eq!(round_trip("<b>a<red>b</red>c</b>"), "<b>a<red>b</red>c</b>");
+        assert_eq!(round_trip("<red>a</red><blue>b</blue>"), "<red>a</red><blue>b</blue>");
+        assert_eq!(round_trip("<red><bold>a</bold></red>"), "<red b>a</red>");
+        assert_eq!(round_trip("<b><i>a</i></b><i>b</i>"), "<i><b>a</b>b</i>");
+        assert_eq!(round_trip("<red>a</red><b><i>b</i>c</b>"), "<red>a</red><b><i>b</i>c</b>");
+    }
+
+    #[test]
+    fn test_to_markup_colors() {
+        assert_eq!(round_trip("<fg=bright red><bg=green>a</bg></fg>"), "<color=bright_red bg_green>a</color>");
+        assert_eq!(round_trip("<color=208 bg=#FF8800>a</color>"), "<color=208 bg=#ff8800>a</color>");
+        assert_eq!(round_trip("<style=\"bright_black on bright_white\">a</style>"), "<color=bright_black bg=bright_white>a</color>");
+    }
+
+    #[test]
+    fn test_to_markup_underlines_and_links() {
+        assert_eq!(round_trip("<cu=red>a</cu>"), "<cu=red>a</cu>");
+        assert_eq!(round_trip("<u=#102030><uu>a</uu></u>"), "<u=#102030 uu>a</u>");
+        assert_eq!(
+            round_trip("<link=\"https://x.dev/a b?q=\\\"1\\\"\">a</link>"),
+            "<link=\"https://x.dev/a b?q=\\\"1\\\"\">a</link>"
+        );
+        assert_eq!(round_trip("<link=https://x.dev/?a=b>x</link>"), "<link=https://x.dev/?a=b>x</link>");
+    }
+
+    #[test]
+    fn test_to_markup_escapes_text() {
+        assert_eq!(round_trip("a <<b> <red><<</red>"), "a <<b> <red><<</red>");
+        assert_eq!(round_trip("<clear>x</clear>"), "x");
+        assert_eq!(round_trip("<b>a<br>b<hr/></b><hr/>"), "<b>a\nb<hr/></b><hr/>");
+    }
+
+    #[test]
+    fn test_to_markup_manual_spans() {
+        let rich = RichString::new(vec![
+            Span::new("a", Style::new().underline_color(Color::Blue).inverse().dim()),
+            Span::new("b", Style::new().hidden().blink().overline().strikethrough()),
+            Span::new("", Style::new().bold()),
+        ]);
+        let markup = rich.to_markup();
+        assert_eq!(markup, "<dim inverse underline_color=blue>a</dim><o s blink hidden>b</o>");
+        assert_eq!(RichString::try_parse(&markup).unwrap(), rich.normalize());
+
+        // Quotes in a value are escaped, so `>` in the text after it stays text
+        let rich = RichString::new(vec![
+            Span::new("x", Style::new().url("https://x?a=\"b")),
+            Span::new("y\"z>w", Style::default()),
+        ]);
+        let markup = rich.to_markup();
+        assert_eq!(markup, "<link=\"https://x?a=\\\"b\">x</link>y\"z>w");
+        assert_eq!(RichString::try_parse(&markup).unwrap(), rich.normalize());
+    }
+}
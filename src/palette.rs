@@ -0,0 +1,171 @@
+//! Color tables and nearest-color matching used when downgrading colors.
+
+use crate::style::Color;
+
+/// The 16 basic colors as rendered by xterm's default palette.
+pub(crate) const ANSI16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The RGB values used for the 16 basic colors and the default foreground and
+/// background when a [`RichString`](crate::RichString) is exported to a format
+/// that has no notion of terminal colors, such as HTML.
+///
+/// The default is xterm's palette on a black background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// The basic colors, from `Black` (0) to `BrightWhite` (15).
+    pub colors: [(u8, u8, u8); 16],
+    pub foreground: (u8, u8, u8),
+    pub background: (u8, u8, u8),
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            colors: ANSI16,
+            foreground: ANSI16[7],
+            background: ANSI16[0],
+        }
+    }
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn foreground(mut self, rgb: (u8, u8, u8)) -> Self {
+        self.foreground = rgb;
+        self
+    }
+
+    pub fn background(mut self, rgb: (u8, u8, u8)) -> Self {
+        self.background = rgb;
+        self
+    }
+
+    /// Returns the RGB value of `color`, or `None` for `Color::Default`.
+    ///
+    /// ```rust
+    /// use richparse::{Color, Palette};
+    ///
+    /// let palette = Palette::default();
+    /// assert_eq!(palette.rgb(Color::Red), Some((205, 0, 0)));
+    /// assert_eq!(palette.rgb(Color::Ansi256(208)), Some((255, 135, 0)));
+    /// assert_eq!(palette.rgb(Color::Default), None);
+    /// ```
+    pub fn rgb(&self, color: Color) -> Option<(u8, u8, u8)> {
+        match color {
+            Color::Default => None,
+            Color::Rgb(r, g, b) => Some((r, g, b)),
+            Color::Ansi256(n) if n < 16 => Some(self.colors[n as usize]),
+            Color::Ansi256(n) => Some(ansi256_to_rgb(n)),
+            basic => basic.ansi16_index().map(|i| self.colors[i as usize]),
+        }
+    }
+}
+
+/// The intensity levels of the 6x6x6 color cube (indices 16-231).
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Returns the RGB value of an xterm-256 color.
+pub(crate) fn ansi256_to_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=15 => ANSI16[n as usize],
+        16..=231 => {
+            let i = n - 16;
+            (
+                CUBE_LEVELS[(i / 36) as usize],
+                CUBE_LEVELS[(i / 6 % 6) as usize],
+                CUBE_LEVELS[(i % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + (n - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+/// Returns the xterm-256 color closest to `rgb`, considering the color cube and
+/// the grayscale ramp (but not the 16 basic colors, whose values vary between terminals).
+pub(crate) fn rgb_to_ansi256(rgb: (u8, u8, u8)) -> u8 {
+    let nearest_level = |c: u8| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| (c as i32 - **level as i32).abs())
+            .map(|(i, _)| i as u8)
+            .unwrap_or(0)
+    };
+    let (r, g, b) = (nearest_level(rgb.0), nearest_level(rgb.1), nearest_level(rgb.2));
+    let cube = 16 + 36 * r + 6 * g + b;
+
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray = 232 + ((average.saturating_sub(3)) / 10).min(23) as u8;
+
+    if distance(rgb, ansi256_to_rgb(gray)) < distance(rgb, ansi256_to_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// Returns the index (0-15) of the basic color closest to `rgb`.
+pub(crate) fn nearest_ansi16(rgb: (u8, u8, u8)) -> u8 {
+    (0..16u8)
+        .min_by_key(|&i| distance(rgb, ANSI16[i as usize]))
+        .unwrap_or(0)
+}
+
+/// A perceptual distance between two colors (squared "redmean" approximation),
+/// weighting the channels the way the eye does.
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let mean_r = (a.0 as i32 + b.0 as i32) / 2;
+    let dr = a.0 as i32 - b.0 as i32;
+    let dg = a.1 as i32 - b.1 as i32;
+    let db = a.2 as i32 - b.2 as i32;
+    ((((512 + mean_r) * dr * dr) >> 8) + 4 * dg * dg + (((767 - mean_r) * db * db) >> 8)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ansi256_round_trip() {
+        for n in 16..=255u8 {
+            assert_eq!(rgb_to_ansi256(ansi256_to_rgb(n)), n);
+        }
+    }
+
+    #[test]
+    fn test_rgb_to_ansi256() {
+        assert_eq!(rgb_to_ansi256((255, 135, 0)), 208);
+        assert_eq!(rgb_to_ansi256((0x20, 0x20, 0x20)), 234);
+    }
+
+    #[test]
+    fn test_nearest_ansi16() {
+        assert_eq!(nearest_ansi16((250, 10, 10)), 9);
+        assert_eq!(nearest_ansi16((180, 20, 20)), 1);
+        assert_eq!(nearest_ansi16((20, 20, 20)), 0);
+        assert_eq!(nearest_ansi16((140, 140, 140)), 8);
+    }
+}
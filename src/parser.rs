@@ -1,7 +1,15 @@
+use crate::error::ParseError;
+use crate::html::{HtmlOptions, HtmlRenderer};
+use crate::registry::TagRegistry;
+use crate::render::{AnsiRenderer, PlainText, RenderOptions};
 use crate::span::Span;
+use crate::svg::SvgOptions;
+use crate::truncate::Ellipsis;
+use crate::wrap::WrapOptions;
 use crate::style::{Color, Style};
 use std::fmt;
 use std::borrow::Cow;
+use std::ops::Range;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RichString<'a> {
@@ -19,161 +27,1064 @@ impl<'a> RichString<'a> {
         }
     }
 
-    pub fn parse(input: &'a str) -> Self {
-        let mut spans = Vec::new();
-        let mut style_stack = vec![Style::default()];
-        
-        let mut cursor = 0;
-        while let Some(rel_pos) = input[cursor..].find('<') {
-            let pos = cursor + rel_pos;
-            
-            // Text before the tag
-            if pos > cursor {
-                let text = &input[cursor..pos];
-                let current_style = style_stack.last().cloned().unwrap_or_default();
-                spans.push(Span::new(text, current_style));
+    /// Writes this string as markup that parses back to the same spans.
+    ///
+    /// The markup is canonical: tags stay open while they apply, colors use
+    /// their names where possible, and a literal `<` is escaped as `<<`. Spans
+    /// may be split or merged differently after parsing it again, so compare
+    /// the results with [`RichString::normalize`].
+    ///
+    /// ```rust
+    /// use richparse::{Color, RichString, Span, Style};
+    ///
+    /// let rs = RichString::new(vec![
+    ///     Span::new("Error", Style::new().fg(Color::Red).bold()),
+    ///     Span::new(": 1 < 2", Style::new().fg(Color::Red)),
+    /// ]);
+    /// let markup = rs.to_markup();
+    /// assert_eq!(markup, "<red><b>Error</b>: 1 << 2</red>");
+    /// assert_eq!(RichString::parse(&markup).normalize(), rs.normalize());
+    /// ```
+    pub fn to_markup(&self) -> String {
+        crate::markup::to_markup(self)
+    }
+
+    /// Returns the number of terminal columns this string occupies; for text
+    /// spanning several lines, the width of the widest line.
+    ///
+    /// Width is measured per grapheme cluster using East Asian Width, so wide
+    /// characters such as CJK ideographs and most emoji count as 2 and
+    /// combining marks as 0. Escape sequences in span text are ignored.
+    ///
+    /// A rule counts as 80 columns, the width `Display` draws it with. This
+    /// holds even where a rule is drawn at another width, as by
+    /// [`RichString::render`] with [`RenderOptions::width`].
+    ///
+    /// ```rust
+    /// use richparse::RichString;
+    ///
+    /// assert_eq!(RichString::parse("<red>日本</red> ok").width(), 7);
+    /// ```
+    pub fn width(&self) -> usize {
+        let mut widest = 0;
+        let mut line = 0;
+        for span in &self.spans {
+            if span.is_rule() {
+                line += crate::render::DEFAULT_WIDTH;
+            } else {
+                crate::width::measure(&span.text, &mut line, &mut widest);
             }
+        }
+        widest.max(line)
+    }
+
+    /// Wraps this string into lines of at most `width` columns; see [`RichString::wrap_with`].
+    pub fn wrap(&self, width: usize) -> Vec<RichString<'a>> {
+        self.wrap_with(&WrapOptions::new(width))
+    }
+
+    /// Wraps this string into lines according to `options`.
+    ///
+    /// Lines break at whitespace, including between spans, and every fragment
+    /// keeps the style of the span it came from, so a hyperlink split across
+    /// lines is reopened on each of them. Existing line breaks are kept, and
+    /// whitespace at a break is dropped.
+    ///
+    /// ```rust
+    /// use richparse::{RichString, WrapOptions};
+    ///
+    /// let rs = RichString::parse("- <b>richparse</b> wraps styled text");
+    /// let lines = rs.wrap_with(&WrapOptions::new(16).hanging_indent(2));
+    /// let lines: Vec<String> = lines.iter().map(|line| line.plain().to_string()).collect();
+    /// assert_eq!(lines, ["- richparse", "  wraps styled", "  text"]);
+    /// ```
+    pub fn wrap_with(&self, options: &WrapOptions) -> Vec<RichString<'a>> {
+        crate::wrap::wrap(self, options)
+    }
+
+    /// Shortens this string to at most `width` columns, replacing the removed
+    /// text with `…`.
+    ///
+    /// The cut falls between grapheme clusters, the ellipsis takes the style of
+    /// the text it replaces, and a string that already fits is returned as is.
+    /// Since styles stay on the spans, no hyperlink or style is left open;
+    /// escape sequences embedded in span text are removed from a truncated string.
+    ///
+    /// ```rust
+    /// use richparse::{Ellipsis, RichString};
+    ///
+    /// let rs = RichString::parse("<b>richparse</b>/src/parser.rs");
+    /// assert_eq!(rs.truncate(12, Ellipsis::End).plain().to_string(), "richparse/s…");
+    /// assert_eq!(rs.truncate(12, Ellipsis::Middle).plain().to_string(), "richpa…er.rs");
+    /// ```
+    pub fn truncate(&self, width: usize, ellipsis: Ellipsis) -> RichString<'a> {
+        crate::truncate::truncate(self, width, ellipsis)
+    }
+
+    /// Pads this string with spaces on the left to `width` columns, aligning it to the right.
+    ///
+    /// Strings already at least `width` columns wide are returned unchanged.
+    pub fn pad_left(&self, width: usize) -> RichString<'a> {
+        self.pad(width.saturating_sub(self.width()), 0)
+    }
+
+    /// Pads this string with spaces on the right to `width` columns.
+    pub fn pad_right(&self, width: usize) -> RichString<'a> {
+        self.pad(0, width.saturating_sub(self.width()))
+    }
+
+    /// Centers this string in `width` columns, putting the odd space on the right.
+    ///
+    /// ```rust
+    /// use richparse::RichString;
+    ///
+    /// let rs = RichString::parse("<b>ok</b>").center(5);
+    /// assert_eq!(rs.plain().to_string(), " ok  ");
+    /// assert_eq!(rs.width(), 5);
+    /// ```
+    pub fn center(&self, width: usize) -> RichString<'a> {
+        let padding = width.saturating_sub(self.width());
+        self.pad(padding / 2, padding - padding / 2)
+    }
+
+    /// Returns this string with unstyled spaces added before and after it.
+    fn pad(&self, before: usize, after: usize) -> RichString<'a> {
+        let mut spans = Vec::with_capacity(self.spans.len() + 2);
+        if before > 0 {
+            spans.push(Span::new(" ".repeat(before), Style::default()));
+        }
+        spans.extend(self.spans.iter().cloned());
+        if after > 0 {
+            spans.push(Span::new(" ".repeat(after), Style::default()));
+        }
+        RichString::new(spans)
+    }
+
+    /// Applies the precision, width and alignment of `f`, returning the text
+    /// cut to the precision and the columns of fill before and after it.
+    pub(crate) fn layout(&self, f: &fmt::Formatter<'_>) -> (Cow<'_, RichString<'a>>, usize, usize) {
+        let text = match f.precision() {
+            Some(precision) => Cow::Owned(crate::truncate::cut(self, precision)),
+            None => Cow::Borrowed(self),
+        };
+        let padding = f.width().unwrap_or(0).saturating_sub(text.width());
+        let (before, after) = match f.align() {
+            Some(fmt::Alignment::Right) => (padding, 0),
+            Some(fmt::Alignment::Center) => (padding / 2, padding - padding / 2),
+            Some(fmt::Alignment::Left) | None => (0, padding),
+        };
+        (text, before, after)
+    }
 
-            // Check what follows '<'
-            let remainder = &input[pos + 1..];
-            if remainder.starts_with('<') {
-                // Escaped "<<" -> "<"
-                let current_style = style_stack.last().cloned().unwrap_or_default();
-                spans.push(Span::new("<", current_style));
-                cursor = pos + 2;
+    /// Merges adjacent spans with the same style and drops empty spans.
+    ///
+    /// Two strings that display the same way are equal once normalized.
+    pub fn normalize(self) -> Self {
+        let mut spans: Vec<Span<'a>> = Vec::with_capacity(self.spans.len());
+        for span in self.spans {
+            if span.text.is_empty() && !span.is_rule() {
                 continue;
             }
+            match spans.last_mut() {
+                Some(last) if last.style == span.style && !last.is_rule() && !span.is_rule() => {
+                    last.text.to_mut().push_str(&span.text)
+                }
+                _ => spans.push(span),
+            }
+        }
+        Self { spans }
+    }
+
+    /// Writes the ANSI rendering of this string to `out`.
+    ///
+    /// Only the attributes that change between adjacent spans are emitted, and
+    /// the terminal is reset once at the end; see [`AnsiRenderer`].
+    pub fn write_ansi<W: fmt::Write>(&self, out: &mut W) -> fmt::Result {
+        self.write_ansi_with(out, &RenderOptions::default())
+    }
+
+    /// Writes the ANSI rendering of this string to `out` using `options`.
+    pub fn write_ansi_with<W: fmt::Write>(&self, out: &mut W, options: &RenderOptions) -> fmt::Result {
+        let mut renderer = AnsiRenderer::with_options(*options);
+        for span in &self.spans {
+            renderer.write_span(out, span)?;
+        }
+        renderer.finish(out)
+    }
+
+    /// Renders this string to ANSI escape sequences using `options`.
+    ///
+    /// ```rust
+    /// use richparse::{ColorDepth, RenderOptions, RichString};
+    ///
+    /// let rs = RichString::parse("<color=#ff8700>Orange</color>");
+    /// let options = RenderOptions::new().color_depth(ColorDepth::Ansi256);
+    /// assert_eq!(rs.render(&options), "\x1b[38;5;208mOrange\x1b[0m");
+    /// ```
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let mut out = String::new();
+        let _ = self.write_ansi_with(&mut out, options);
+        out
+    }
+
+    /// Writes this string to `out` as HTML; see [`HtmlRenderer`].
+    pub fn write_html<W: fmt::Write>(&self, out: &mut W, options: &HtmlOptions) -> fmt::Result {
+        let mut renderer = HtmlRenderer::with_options(options.clone());
+        for span in &self.spans {
+            renderer.write_span(out, span)?;
+        }
+        renderer.finish(out)
+    }
+
+    /// Renders this string to HTML using `options`.
+    pub fn to_html(&self, options: &HtmlOptions) -> String {
+        let mut out = String::new();
+        let _ = self.write_html(&mut out, options);
+        out
+    }
+
+    /// Renders this string as a standalone SVG image of a terminal window; see
+    /// [`svg::render_lines`](crate::svg::render_lines).
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        crate::svg::render_lines(std::slice::from_ref(self), options)
+    }
+
+    /// Returns a [`Display`](fmt::Display) adapter that writes only the text of
+    /// this string, without any escape sequences.
+    ///
+    /// Useful for log files, piped output and snapshot tests.
+    pub fn plain(&self) -> PlainText<'_, 'a> {
+        PlainText::new(self)
+    }
+
+    /// Parses text that already contains ANSI escape sequences, such as the
+    /// colored output of another program.
+    ///
+    /// SGR sequences (colors, modifiers, underline styles and colors) and OSC 8
+    /// hyperlinks become the style of the resulting spans; other escape sequences
+    /// are dropped. Text is borrowed from `input`.
+    ///
+    /// ```rust
+    /// use richparse::{Color, RichString, Style};
+    ///
+    /// let rs = RichString::from_ansi("\x1b[1;31merror\x1b[0m: not found");
+    /// assert_eq!(rs.spans[0].style, Style::new().fg(Color::Red).bold());
+    /// assert_eq!(rs.plain().to_string(), "error: not found");
+    /// ```
+    pub fn from_ansi(input: &'a str) -> Self {
+        crate::ansi::parse_ansi(input)
+    }
+
+    /// Parses markup, silently ignoring anything it does not understand.
+    ///
+    /// Unknown tags, invalid colors, stray closing tags and unterminated `<`
+    /// never fail; use [`RichString::try_parse`] to reject them instead.
+    pub fn parse(input: &'a str) -> Self {
+        match parse_markup(input, &ParseOptions::new()) {
+            Ok(rs) => rs,
+            Err(_) => unreachable!("lenient parsing never fails"),
+        }
+    }
+
+    /// Parses markup in strict mode, returning the first problem found.
+    ///
+    /// ```rust
+    /// use richparse::{ParseError, RichString};
+    ///
+    /// let err = RichString::try_parse("<bodl>Oops</bodl>").unwrap_err();
+    /// assert!(matches!(err, ParseError::UnknownTag { .. }));
+    /// assert_eq!(err.span(), 0..6);
+    /// ```
+    pub fn try_parse(input: &'a str) -> Result<Self, ParseError> {
+        parse_markup(input, &ParseOptions::new().strict())
+    }
+
+    /// Parses markup with explicit [`ParseOptions`].
+    ///
+    /// ```rust
+    /// use richparse::{ClosePolicy, Color, ParseOptions, RichString};
+    ///
+    /// let options = ParseOptions::new().close_policy(ClosePolicy::Pop);
+    /// let rs = RichString::parse_with_options("<red><b>x</red>y</b>", &options).unwrap();
+    /// assert_eq!(rs.spans[1].style.fg, Color::Red);
+    /// ```
+    pub fn parse_with_options(input: &'a str, options: &ParseOptions<'_>) -> Result<Self, ParseError> {
+        parse_markup(input, options)
+    }
+
+    /// Parses markup leniently, resolving custom tags through `registry`
+    /// before falling back to the built-in tags.
+    pub fn parse_with(registry: &TagRegistry, input: &'a str) -> Self {
+        match parse_markup(input, &ParseOptions::new().registry(registry)) {
+            Ok(rs) => rs,
+            Err(_) => unreachable!("lenient parsing never fails"),
+        }
+    }
+}
+
+/// How closing tags are matched against open tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClosePolicy {
+    /// Close the most recent open tag with the same name, along with every tag
+    /// opened after it. Closing tags that match nothing are ignored.
+    #[default]
+    AutoClose,
+    /// Report a closing tag that does not match the most recent open tag as an error.
+    Error,
+    /// Close the most recent open tag regardless of its name.
+    Pop,
+}
+
+/// Options controlling how markup is parsed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions<'r> {
+    pub strict: bool,
+    pub close_policy: ClosePolicy,
+    pub registry: Option<&'r TagRegistry>,
+}
+
+impl<'r> ParseOptions<'r> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports every problem in the markup as an error. Implies [`ClosePolicy::Error`].
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self.close_policy = ClosePolicy::Error;
+        self
+    }
+
+    pub fn close_policy(mut self, policy: ClosePolicy) -> Self {
+        self.close_policy = policy;
+        self
+    }
+
+    /// Resolves custom tags through `registry` before the built-in tags.
+    pub fn registry(mut self, registry: &'r TagRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+}
+
+/// A lexical unit of markup, produced by [`Tokens`].
+pub(crate) enum Token<'a> {
+    /// Plain text between tags.
+    Text(&'a str),
+    /// An escaped `<<`, standing for a literal `<`.
+    Escaped,
+    /// The content of an opening tag, e.g. `color=red` for `<color=red>`.
+    Open(&'a str),
+    /// The content of a closing tag without the leading `/`.
+    Close(&'a str),
+    /// A `<` with no matching `>`.
+    Unterminated,
+}
+
+/// Splits markup into tokens, each paired with its byte range in the input.
+#[derive(Debug, Clone)]
+pub(crate) struct Tokens<'a> {
+    input: &'a str,
+    cursor: usize,
+    /// Whether more input may follow, in which case tokenizing stops before a
+    /// tag that is not complete yet instead of treating its `<` as text.
+    partial: bool,
+}
+
+impl<'a> Tokens<'a> {
+    pub(crate) fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            cursor: 0,
+            partial: false,
+        }
+    }
+
+    /// Tokenizes the start of input that continues after `input`.
+    pub(crate) fn partial(input: &'a str) -> Self {
+        Self {
+            partial: true,
+            ..Self::new(input)
+        }
+    }
+
+    /// The byte offset in the input up to which tokens have been produced.
+    pub(crate) fn cursor(&self) -> usize {
+        self.cursor
+    }
+}
 
-            // Look for closing '>'
-            if let Some(tag_end_rel) = remainder.find('>') {
-                let tag_content = &remainder[..tag_end_rel];
-                let tag_end_abs = pos + 1 + tag_end_rel + 1; // +1 for '<', +1 for '>'
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (Token<'a>, Range<usize>);
 
-                if tag_content.starts_with('/') {
-                    // Closing tag e.g. "</red>"
-                    if style_stack.len() > 1 {
-                        style_stack.pop();
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.input;
+        let start = self.cursor;
+        if start >= input.len() {
+            return None;
+        }
+
+        let pos = match input[start..].find('<') {
+            Some(rel_pos) => start + rel_pos,
+            None => input.len(),
+        };
+
+        // Text before the tag
+        if pos > start {
+            self.cursor = pos;
+            return Some((Token::Text(&input[start..pos]), start..pos));
+        }
+
+        // Check what follows '<'
+        let remainder = &input[pos + 1..];
+        if self.partial && remainder.is_empty() {
+            // Could still become "<<" or a tag
+            return None;
+        }
+        if remainder.starts_with('<') {
+            // Escaped "<<" -> "<"
+            self.cursor = pos + 2;
+            return Some((Token::Escaped, pos..pos + 2));
+        }
+
+        // Look for closing '>'
+        if let Some(tag_end_rel) = find_tag_end(remainder, self.partial) {
+            let tag_content = &remainder[..tag_end_rel];
+            let tag_end_abs = pos + 1 + tag_end_rel + 1; // +1 for '<', +1 for '>'
+            self.cursor = tag_end_abs;
+
+            let token = match tag_content.strip_prefix('/') {
+                Some(name) => Token::Close(name),
+                None => Token::Open(tag_content),
+            };
+            Some((token, pos..tag_end_abs))
+        } else if self.partial {
+            // The '>' may be in the input that follows
+            None
+        } else {
+            // No closing '>', treat '<' as literal text
+            self.cursor = pos + 1;
+            Some((Token::Unterminated, pos..pos + 1))
+        }
+    }
+}
+
+/// Finds the `>` ending the tag at the start of `s`, skipping over quoted
+/// attribute values such as `link="a>b"`.
+///
+/// If a quoted value is never closed, the quotes are treated as plain text and
+/// the tag ends at the first `>`, unless `partial` is set because the closing
+/// quote may still follow.
+fn find_tag_end(s: &str, partial: bool) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'>' => return Some(i),
+            b'=' if matches!(bytes.get(i + 1), Some(b'"' | b'\'')) => match closing_quote(&s[i + 1..]) {
+                Some(len) => i += 1 + len,
+                None if partial => return None,
+                None => return s.find('>'),
+            },
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Given `s` starting with a quote, returns the byte index of the matching
+/// closing quote, honoring backslash escapes.
+fn closing_quote(s: &str) -> Option<usize> {
+    let quote = s.as_bytes()[0];
+    let mut escaped = false;
+    for (i, &b) in s.as_bytes().iter().enumerate().skip(1) {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            _ if b == quote => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// A single `key` or `key=value` attribute of a tag.
+pub(crate) struct Attribute<'a> {
+    pub(crate) key: &'a str,
+    pub(crate) value: Option<Cow<'a, str>>,
+    /// The value as written, inside any quotes and with its escapes.
+    raw: &'a str,
+    /// Byte offset of `raw` within the tag content.
+    value_start: usize,
+}
+
+/// Splits tag content such as `color=red bg="bright blue" bold` into attributes.
+///
+/// Values may be quoted with `"` or `'`, in which case they can contain
+/// whitespace, `=` and `>`, and `\` escapes the next character. For
+/// compatibility, an unquoted `bright` value absorbs the following word, so
+/// `color=bright red` still means bright red.
+pub(crate) fn parse_attributes(tag: &str) -> Vec<Attribute<'_>> {
+    let mut attributes = Vec::new();
+    let mut rest = tag.trim_start();
+
+    while !rest.is_empty() {
+        let start = offset_in(tag, rest);
+        let key_len = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let key = &rest[..key_len];
+        rest = &rest[key_len..];
+
+        let mut value = None;
+        let mut raw = "";
+        let mut value_start = start + key_len;
+        if let Some(after_eq) = rest.strip_prefix('=') {
+            value_start += 1;
+            let quoted = after_eq.starts_with(['"', '\'']);
+            let (written, unescaped, len) = match closing_quote(after_eq).filter(|_| quoted) {
+                Some(end) => {
+                    value_start += 1;
+                    let written = &after_eq[1..end];
+                    (written, unescape_quoted(written), end + 1)
+                }
+                None => {
+                    let mut end = after_eq.find(char::is_whitespace).unwrap_or(after_eq.len());
+                    // "bright red" may be written as two words
+                    if after_eq[..end].eq_ignore_ascii_case("bright") {
+                        let next = after_eq[end..].trim_start();
+                        let next_len = next.find(char::is_whitespace).unwrap_or(next.len());
+                        if next_len > 0 && !next[..next_len].contains('=') {
+                            end = offset_in(after_eq, next) + next_len;
+                        }
                     }
-                } else {
-                    // Opening tag e.g. "<red>" or "<color=red>"
-                    let current_style = style_stack.last().cloned().unwrap_or_default();
-                    let new_style = apply_tag(tag_content, current_style);
-                    style_stack.push(new_style);
+                    (&after_eq[..end], Cow::Borrowed(&after_eq[..end]), end)
                 }
-                cursor = tag_end_abs;
-            } else {
-                // No closing '>', treat '<' as literal text
-                let current_style = style_stack.last().cloned().unwrap_or_default();
-                spans.push(Span::new("<", current_style));
-                cursor = pos + 1;
+            };
+            value = Some(unescaped);
+            raw = written;
+            rest = &after_eq[len..];
+        }
+
+        attributes.push(Attribute { key, value, raw, value_start });
+        rest = rest.trim_start();
+    }
+
+    attributes
+}
+
+/// Removes backslash escapes from a quoted value, borrowing when there are none.
+fn unescape_quoted(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Maps a byte offset into the unescaped form of the quoted value `raw` back to
+/// the matching offset in `raw`.
+fn raw_offset(raw: &str, unescaped: usize) -> usize {
+    let mut count = 0;
+    let mut chars = raw.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if count >= unescaped {
+            return i;
+        }
+        match c {
+            '\\' => count += chars.next().map_or(0, |(_, c)| c.len_utf8()),
+            c => count += c.len_utf8(),
+        }
+    }
+    raw.len()
+}
+
+/// An open tag on the style stack.
+#[derive(Debug)]
+pub(crate) struct Frame<'a> {
+    style: Style<'a>,
+    tag: Cow<'a, str>,
+    /// Canonical tag name used to match closing tags.
+    name: String,
+    span: Range<usize>,
+}
+
+impl Frame<'_> {
+    /// The bottom frame, which holds the default style and is never closed.
+    pub(crate) fn base() -> Self {
+        Frame {
+            style: Style::default(),
+            tag: Cow::Borrowed(""),
+            name: String::new(),
+            span: 0..0,
+        }
+    }
+
+    pub(crate) fn into_owned(self) -> Frame<'static> {
+        Frame {
+            style: self.style.into_owned(),
+            tag: Cow::Owned(self.tag.into_owned()),
+            name: self.name,
+            span: self.span,
+        }
+    }
+}
+
+fn current_style<'a>(stack: &[Frame<'a>]) -> Style<'a> {
+    stack.last().map(|f| f.style.clone()).unwrap_or_default()
+}
+
+/// Why a single tag could not be applied.
+pub(crate) enum TagError<'a> {
+    /// An attribute with an unknown key.
+    Unknown(&'a str),
+    /// An attribute with an invalid value, with spans already resolved.
+    Invalid(ParseError),
+}
+
+impl<'a> TagError<'a> {
+    /// Converts this error into a [`ParseError`] for the tag found at `span`.
+    pub(crate) fn into_parse_error(self, span: Range<usize>) -> ParseError {
+        match self {
+            TagError::Unknown(key) => ParseError::UnknownTag {
+                tag: key.to_string(),
+                span,
+            },
+            TagError::Invalid(error) => error,
+        }
+    }
+}
+
+fn parse_markup<'a>(input: &'a str, options: &ParseOptions<'_>) -> Result<RichString<'a>, ParseError> {
+    let mut spans = Vec::new();
+    let mut stack = vec![Frame::base()];
+    for (token, span) in Tokens::new(input) {
+        apply_token(token, span, &mut stack, &mut spans, options)?;
+    }
+    check_closed(&stack, options)?;
+    Ok(RichString { spans })
+}
+
+/// Applies a single token found at `span` to the style stack, appending the
+/// text it produces to `spans`.
+pub(crate) fn apply_token<'a>(
+    token: Token<'a>,
+    span: Range<usize>,
+    stack: &mut Vec<Frame<'a>>,
+    spans: &mut Vec<Span<'a>>,
+    options: &ParseOptions<'_>,
+) -> Result<(), ParseError> {
+    let strict = options.strict;
+    match token {
+        Token::Text(text) => spans.push(Span::new(text, current_style(stack))),
+        Token::Escaped => spans.push(Span::new("<", current_style(stack))),
+        Token::Unterminated => {
+            if strict {
+                return Err(ParseError::UnterminatedTag { span });
+            }
+            spans.push(Span::new("<", current_style(stack)));
+        }
+        Token::Close(tag) => {
+            // Closing tag e.g. "</red>"
+            close_tag(stack, tag, span, options.close_policy)?;
+        }
+        Token::Open(tag) => {
+            let (tag, self_closing) = split_self_closing(tag);
+            if let Some(element) = inline_element(tag, current_style(stack), options.registry) {
+                // Inline elements such as "<br>" never have content
+                spans.push(element);
+                return Ok(());
+            }
+            // Opening tag e.g. "<red>" or "<color=red>"
+            let mut style = current_style(stack);
+            let tag_start = span.start + 1;
+            if let Err(err) = apply_tag(tag, tag_start, &mut style, options.registry)
+                && strict
+            {
+                return Err(err.into_parse_error(span));
+            }
+            // A self-closing tag such as "<b/>" styles nothing
+            if !self_closing {
+                let name = canonical_tag_name(tag);
+                stack.push(Frame {
+                    style,
+                    tag: Cow::Borrowed(tag),
+                    name,
+                    span,
+                });
             }
         }
+    }
+    Ok(())
+}
+
+/// In strict mode, reports the outermost tag that is still open at the end of the input.
+pub(crate) fn check_closed(stack: &[Frame<'_>], options: &ParseOptions<'_>) -> Result<(), ParseError> {
+    match stack.get(1) {
+        Some(frame) if options.strict => Err(ParseError::UnclosedTag {
+            tag: frame.tag.trim().to_string(),
+            span: frame.span.clone(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Splits the `/` off a self-closing tag such as `<b/>` or `<link=x.dev />`,
+/// returning the tag content and whether it was self-closing.
+///
+/// A `/` directly after an unquoted value is part of the value, so
+/// `<link=https://x.dev/>` is an ordinary opening tag.
+pub(crate) fn split_self_closing(tag: &str) -> (&str, bool) {
+    let Some(rest) = tag.strip_suffix('/') else {
+        return (tag, false);
+    };
+    let last_word = rest.rsplit(char::is_whitespace).next().unwrap_or_default();
+    if rest.ends_with(|c: char| c.is_whitespace() || c == '"' || c == '\'') || !last_word.contains('=') {
+        (rest, true)
+    } else {
+        (tag, false)
+    }
+}
 
-        // Remaining text
-        if cursor < input.len() {
-            let text = &input[cursor..];
-            let current_style = style_stack.last().cloned().unwrap_or_default();
-            spans.push(Span::new(text, current_style));
+/// Returns the span for a built-in inline element, such as `<br>` or `<hr/>`,
+/// drawn in the style of the surrounding text.
+///
+/// Tags registered with the same name take precedence.
+pub(crate) fn inline_element<'a>(tag: &str, style: Style<'a>, registry: Option<&TagRegistry>) -> Option<Span<'a>> {
+    let name = tag.trim().to_lowercase();
+    if registry.is_some_and(|registry| registry.contains(&name)) {
+        return None;
+    }
+    let text = match name.as_str() {
+        "br" => "\n",
+        "tab" => "\t",
+        "nbsp" => "\u{a0}",
+        "hr" => return Some(Span::rule(style)),
+        _ => return None,
+    };
+    Some(Span::new(text, style))
+}
+
+/// Closes the open tag matching `tag` according to `policy`.
+///
+/// An empty closing tag (`</>`) always closes the most recent open tag.
+fn close_tag(stack: &mut Vec<Frame<'_>>, tag: &str, span: Range<usize>, policy: ClosePolicy) -> Result<(), ParseError> {
+    let name = canonical_tag_name(tag);
+    let unbalanced = || ParseError::UnbalancedClose {
+        tag: tag.trim().to_string(),
+        span: span.clone(),
+    };
+
+    // The bottom frame holds the default style and is never closed.
+    if stack.len() == 1 {
+        return match policy {
+            ClosePolicy::Error => Err(unbalanced()),
+            _ => Ok(()),
+        };
+    }
+
+    if name.is_empty() || policy == ClosePolicy::Pop {
+        stack.pop();
+        return Ok(());
+    }
+
+    match stack.iter().skip(1).rposition(|frame| frame.name == name) {
+        Some(index) if policy == ClosePolicy::AutoClose || index + 1 == stack.len() - 1 => {
+            stack.truncate(index + 1);
+            Ok(())
+        }
+        Some(_) => {
+            let top = &stack[stack.len() - 1];
+            Err(ParseError::MismatchedClose {
+                expected: top.tag.trim().to_string(),
+                found: tag.trim().to_string(),
+                span,
+            })
         }
+        None if policy == ClosePolicy::Error => Err(unbalanced()),
+        None => Ok(()),
+    }
+}
 
-        Self { spans }
+/// Returns the lowercase name of a tag with aliases folded together,
+/// e.g. `bold` for both `<b>` and `<BOLD>`, `color` for `<fg=red>` and `<red>`,
+/// or `background` for `<bg_red>`.
+pub(crate) fn canonical_tag_name(tag: &str) -> String {
+    let tag = tag.trim_start();
+    let name = tag[..tag.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(tag.len())].to_lowercase();
+    let canonical = match name.as_str() {
+        "b" => "bold",
+        "i" => "italic",
+        "u" => "underline",
+        "uu" => "dunderline",
+        "cu" => "cunderline",
+        "o" => "overline",
+        "s" => "strikethrough",
+        "fg" | "black" | "red" | "green" | "yellow" | "blue" | "magenta" | "cyan" | "white" => "color",
+        "bg" => "background",
+        _ if name.starts_with("bg_") => "background",
+        _ => return name,
+    };
+    canonical.to_string()
+}
+
+/// Returns the byte offset of `sub` within `input`; `sub` must be a subslice of `input`.
+fn offset_in(input: &str, sub: &str) -> usize {
+    sub.as_ptr() as usize - input.as_ptr() as usize
+}
+
+/// Applies every attribute of a tag to `style`, e.g. `color=red bg=blue bold`.
+///
+/// All valid attributes are applied even if some fail; the first failure is
+/// returned. `offset` is the position of the tag content in the input.
+pub(crate) fn apply_tag<'a>(tag: &'a str, offset: usize, style: &mut Style<'a>, registry: Option<&TagRegistry>) -> Result<(), TagError<'a>> {
+    let mut result = Ok(());
+    let attributes = parse_attributes(tag);
+    if attributes.is_empty() {
+        result = Err(TagError::Unknown(""));
     }
+
+    for Attribute { key, value, raw, value_start } in attributes {
+        let lower = key.to_lowercase();
+
+        // Custom tags take precedence over the built-in ones
+        if let Some(registry) = registry
+            && registry.contains(&lower)
+        {
+            match value {
+                Some(Cow::Owned(value)) => {
+                    // The value only lives for this call, so the result must not borrow it
+                    let mut custom: Style<'_> = std::mem::take(style);
+                    registry.apply(&lower, Some(&value), &mut custom);
+                    *style = custom.into_owned();
+                }
+                Some(Cow::Borrowed(value)) => {
+                    registry.apply(&lower, Some(value), style);
+                }
+                None => {
+                    registry.apply(&lower, None, style);
+                }
+            }
+            continue;
+        }
+
+        let applied = match value {
+            Some(value) => apply_attribute(&lower, key, value, raw, offset + value_start, style),
+            None => apply_flag(&lower, style).map_err(|_| TagError::Unknown(key)),
+        };
+        if result.is_ok() {
+            result = applied;
+        }
+    }
+    result
 }
 
-fn apply_tag<'a>(tag: &'a str, mut style: Style<'a>) -> Style<'a> {
-    let parts: Vec<&str> = tag.split('=').collect();
-    let key = parts[0].trim().to_lowercase();
-    let val_raw = if parts.len() > 1 { Some(parts[1].trim()) } else { None };
-    let val_lower = val_raw.map(|v| v.to_lowercase());
+/// Applies a `key=value` attribute such as `color=red` or `link=https://...`.
+///
+/// `raw` is the value as written, which differs from `val_raw` when it is quoted
+/// and has escapes, and `value_start` is its position in the text being parsed.
+/// Both are used for error spans.
+fn apply_attribute<'a>(
+    lower: &str,
+    key: &'a str,
+    val_raw: Cow<'a, str>,
+    raw: &str,
+    value_start: usize,
+    style: &mut Style<'a>,
+) -> Result<(), TagError<'a>> {
+    let value_span = value_start..value_start + raw.len();
+    let color = |val: &str| {
+        let normalized = val.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        parse_color(&normalized).ok_or_else(|| TagError::Invalid(ParseError::InvalidColor {
+            value: val.to_string(),
+            span: value_span.clone(),
+        }))
+    };
 
-    match (key.as_str(), val_lower.as_deref()) {
+    match lower {
         // Explicit color=...
-        ("color" | "fg", Some(val)) => {
-            if let Some(c) = parse_color(val) {
-                style.fg = c;
-            }
+        "color" | "fg" => {
+            style.fg = color(&val_raw)?;
         }
         // Explicit bg=...
-        ("background" | "bg", Some(val)) => {
-            if let Some(c) = parse_color(val) {
-                style.bg = c;
-            }
+        "background" | "bg" => {
+            style.bg = color(&val_raw)?;
         }
         
         // Explicit link=...
-        ("link", _) => {
-            if let Some(raw) = val_raw {
-                style.url = Some(Cow::Borrowed(raw));
-            }
+        "link" => {
+            style.url = Some(val_raw);
         }
 
         // Explicit curly underline with color
-        ("cu" | "cunderline", Some(val)) => {
+        "cu" | "cunderline" => {
             style.curly_underline = true;
-            if let Some(c) = parse_color(val) {
-                style.underline_color = Some(c);
-            }
+            style.underline_color = Some(color(&val_raw)?);
         }
 
         // Explicit underline with color
-        ("u" | "underline", Some(val)) => {
+        "u" | "underline" => {
             style.underline = true;
-            if let Some(c) = parse_color(val) {
-                style.underline_color = Some(c);
-            }
+            style.underline_color = Some(color(&val_raw)?);
         }
 
         // Explicit double underline with color
-        ("uu" | "dunderline", Some(val)) => {
+        "uu" | "dunderline" => {
             style.double_underline = true;
-            if let Some(c) = parse_color(val) {
-                style.underline_color = Some(c);
+            style.underline_color = Some(color(&val_raw)?);
+        }
+
+        // Underline color without changing the underline itself
+        "underline_color" => {
+            style.underline_color = Some(color(&val_raw)?);
+        }
+
+        // Style expression, e.g. style="bold red on blue"
+        "style" => {
+            let parsed = parse_style(&val_raw)
+                .map(Style::into_owned)
+                .map_err(|error| {
+                    TagError::Invalid(error.map_span(|span| {
+                        value_start + raw_offset(raw, span.start)..value_start + raw_offset(raw, span.end)
+                    }))
+                })?;
+            *style = std::mem::take(style).merge(&parsed);
+        }
+
+        _ => return Err(TagError::Unknown(key)),
+    }
+    Ok(())
+}
+
+/// Applies a tag without a value, such as `red`, `bg_blue` or `bold`.
+fn apply_flag(key: &str, style: &mut Style<'_>) -> Result<(), ()> {
+    match key {
+        // Colors
+        "black" => style.fg = Color::Black,
+        "red" => style.fg = Color::Red,
+        "green" => style.fg = Color::Green,
+        "yellow" => style.fg = Color::Yellow,
+        "blue" => style.fg = Color::Blue,
+        "magenta" => style.fg = Color::Magenta,
+        "cyan" => style.fg = Color::Cyan,
+        "white" => style.fg = Color::White,
+        
+        // Backgrounds (convention: bg_color)
+        "bg_black" => style.bg = Color::Black,
+        "bg_red" => style.bg = Color::Red,
+        "bg_green" => style.bg = Color::Green,
+        "bg_yellow" => style.bg = Color::Yellow,
+        "bg_blue" => style.bg = Color::Blue,
+        "bg_magenta" => style.bg = Color::Magenta,
+        "bg_cyan" => style.bg = Color::Cyan,
+        "bg_white" => style.bg = Color::White,
+
+        // A link without a target has no effect
+        "link" => {}
+
+        // Reset/Default
+        "clear" => *style = Style::default(),
+
+        // Modifiers
+        k if apply_modifier(k, style) => {}
+        
+        _ => return Err(()),
+    }
+    Ok(())
+}
+
+/// Turns on the modifier called `name`, returning `false` if there is no such modifier.
+fn apply_modifier(name: &str, style: &mut Style<'_>) -> bool {
+    match name {
+        "b" | "bold" => style.bold = true,
+        "i" | "italic" => style.italic = true,
+        "u" | "underline" => style.underline = true,
+        "uu" | "dunderline" => style.double_underline = true,
+        "cu" | "cunderline" => style.curly_underline = true,
+        "o" | "overline" => style.overline = true,
+        "s" | "strikethrough" => style.strikethrough = true,
+        "dim" => style.dim = true,
+        "blink" => style.blink = true,
+        "hidden" => style.hidden = true,
+        "inverse" => style.inverse = true,
+        _ => return false,
+    }
+    true
+}
+
+/// Parses a space-separated style expression such as `bold red on #202020 u=blue`.
+///
+/// Words are modifiers (`bold`, `u`, ...), foreground colors in any form
+/// accepted by `<color=...>`, or `key=value` attributes accepted by tags
+/// (`bg=blue`, `u=red`, `link=https://...`); a color preceded by `on` sets
+/// the background. Error spans are byte offsets into `expr`.
+pub(crate) fn parse_style(expr: &str) -> Result<Style<'_>, ParseError> {
+    let mut style = Style::default();
+    let mut words = expr.split_whitespace();
+    let mut background = false;
+
+    while let Some(word) = words.next() {
+        let start = offset_in(expr, word);
+        let mut end = start + word.len();
+
+        if let Some((key, value)) = word.split_once('=') {
+            let value_start = start + key.len() + 1;
+            if let Err(err) = apply_attribute(&key.to_lowercase(), key, Cow::Borrowed(value), value, value_start, &mut style) {
+                return Err(match err {
+                    TagError::Unknown(_) => ParseError::UnknownStyle {
+                        word: word.to_string(),
+                        span: start..end,
+                    },
+                    TagError::Invalid(error) => error,
+                });
             }
+            continue;
         }
 
-        // Standard tags
-        (k, None) => match k {
-            // Colors
-            "black" => style.fg = Color::Black,
-            "red" => style.fg = Color::Red,
-            "green" => style.fg = Color::Green,
-            "yellow" => style.fg = Color::Yellow,
-            "blue" => style.fg = Color::Blue,
-            "magenta" => style.fg = Color::Magenta,
-            "cyan" => style.fg = Color::Cyan,
-            "white" => style.fg = Color::White,
-            
-            // Backgrounds (convention: bg_color)
-            "bg_black" => style.bg = Color::Black,
-            "bg_red" => style.bg = Color::Red,
-            "bg_green" => style.bg = Color::Green,
-            "bg_yellow" => style.bg = Color::Yellow,
-            "bg_blue" => style.bg = Color::Blue,
-            "bg_magenta" => style.bg = Color::Magenta,
-            "bg_cyan" => style.bg = Color::Cyan,
-            "bg_white" => style.bg = Color::White,
-
-            // Modifiers
-            "b" | "bold" => style.bold = true,
-            "i" | "italic" => style.italic = true,
-            "u" | "underline" => style.underline = true,
-            "uu" | "dunderline" => style.double_underline = true,
-            "cu" | "cunderline" => style.curly_underline = true,
-            "o" | "overline" => style.overline = true,
-            "s" | "strikethrough" => style.strikethrough = true,
-            "dim" => style.dim = true,
-            "blink" => style.blink =true,
-            "hidden" => style.hidden = true,
-            "inverse" => style.inverse = true,
-            
-            // Reset/Default
-            "clear" => style = Style::default(),
-            
-            _ => {}
-        },
-        _ => {}
+        let mut name = word.to_lowercase();
+
+        if name == "on" && !background {
+            background = true;
+            continue;
+        }
+
+        // "bright red" may be written as two words
+        if name == "bright" && let Some(next) = words.next() {
+            name = format!("bright {}", next.to_lowercase());
+            end = offset_in(expr, next) + next.len();
+        }
+
+        if background {
+            style.bg = parse_color(&name).ok_or_else(|| ParseError::InvalidColor {
+                value: expr[start..end].to_string(),
+                span: start..end,
+            })?;
+            background = false;
+        } else if let Some(color) = parse_color(&name) {
+            style.fg = color;
+        } else if !apply_modifier(&name, &mut style) {
+            return Err(ParseError::UnknownStyle {
+                word: expr[start..end].to_string(),
+                span: start..end,
+            });
+        }
+    }
+
+    if background {
+        // "on" without a color
+        return Err(ParseError::InvalidColor {
+            value: String::new(),
+            span: expr.len()..expr.len(),
+        });
     }
-    style
+
+    Ok(style)
 }
 
 fn parse_color(name: &str) -> Option<Color> {
@@ -196,7 +1107,7 @@ fn parse_color(name: &str) -> Option<Color> {
         "white" => Some(Color::White),
         _ => {
             // Check for hex color: #RRGGBB
-            if name.starts_with('#') && name.len() == 7 {
+            if name.starts_with('#') && name.len() == 7 && name.is_ascii() {
                 let r = u8::from_str_radix(&name[1..3], 16).ok();
                 let g = u8::from_str_radix(&name[3..5], 16).ok();
                 let b = u8::from_str_radix(&name[5..7], 16).ok();
@@ -212,10 +1123,35 @@ fn parse_color(name: &str) -> Option<Color> {
     }
 }
 
+/// Writes the ANSI rendering, honoring the formatter's width, fill, alignment
+/// and precision by visible width rather than bytes.
+///
+/// Precision cuts the text to that many columns, like it does for `str`, and
+/// padding is written unstyled around the styled text. Strings are left-aligned
+/// by default. Rules are drawn 80 columns wide; use [`RichString::render`] with
+/// [`RenderOptions::width`] for another width.
+///
+/// ```rust
+/// use richparse::rich;
+///
+/// let rs = rich!("<red>日本</red>");
+/// assert_eq!(format!("[{:>6}]", rs), "[  \x1b[31m日本\x1b[0m]");
+/// assert_eq!(format!("[{:-^7.2}]", rs), "[--\x1b[31m日\x1b[0m---]");
+/// ```
 impl<'a> fmt::Display for RichString<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for span in &self.spans {
-            write!(f, "{}", span)?;
+        if f.width().is_none() && f.precision().is_none() {
+            return self.write_ansi(f);
+        }
+
+        let (text, before, after) = self.layout(f);
+        let fill = f.fill();
+        for _ in 0..before {
+            write!(f, "{}", fill)?;
+        }
+        text.write_ansi(f)?;
+        for _ in 0..after {
+            write!(f, "{}", fill)?;
         }
         Ok(())
     }
@@ -229,6 +1165,7 @@ impl<'a, 'source> From<RichString<'a>> for fluent_bundle::FluentValue<'source> {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -367,4 +1304,314 @@ mod tests {
         assert_eq!(rs_owned.spans.len(), 1);
         assert_eq!(rs_owned.spans[0].text, "Owned");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_try_parse_valid() {
+        let rs = RichString::try_parse("<red>R <b>B</b></red> <<tag").unwrap();
+        assert_eq!(rs, RichString::parse("<red>R <b>B</b></red> <<tag"));
+    }
+
+    #[test]
+    fn test_try_parse_unknown_tag() {
+        let err = RichString::try_parse("Hi <bodl>x</bodl>").unwrap_err();
+        assert_eq!(err, ParseError::UnknownTag { tag: "bodl".to_string(), span: 3..9 });
+    }
+
+    #[test]
+    fn test_try_parse_invalid_color() {
+        let err = RichString::try_parse("<color=#GG0000>x</color>").unwrap_err();
+        assert_eq!(err, ParseError::InvalidColor { value: "#GG0000".to_string(), span: 7..14 });
+
+        let err = RichString::try_parse("<u=nope>x</u>").unwrap_err();
+        assert_eq!(err.span(), 3..7);
+    }
+
+    #[test]
+    fn test_try_parse_unbalanced_close() {
+        let err = RichString::try_parse("x</red>").unwrap_err();
+        assert_eq!(err, ParseError::UnbalancedClose { tag: "red".to_string(), span: 1..7 });
+    }
+
+    #[test]
+    fn test_try_parse_unclosed_tag() {
+        let err = RichString::try_parse("a <red>b <b>c</b>").unwrap_err();
+        assert_eq!(err, ParseError::UnclosedTag { tag: "red".to_string(), span: 2..7 });
+    }
+
+    #[test]
+    fn test_try_parse_unterminated() {
+        let err = RichString::try_parse("1 < 2").unwrap_err();
+        assert_eq!(err, ParseError::UnterminatedTag { span: 2..3 });
+        // Lenient parsing keeps the '<' as text
+        assert_eq!(RichString::parse("1 < 2").spans[1].text, "<");
+    }
+
+    #[test]
+    fn test_parse_lenient_keeps_partial_style() {
+        let rs = RichString::parse("<u=nope>x</u>");
+        assert!(rs.spans[0].style.underline);
+        assert_eq!(rs.spans[0].style.underline_color, None);
+    }
+
+    #[test]
+    fn test_close_tag_matches_name() {
+        let rs = RichString::parse("<red><b>x</red>y</b>");
+        assert_eq!(rs.spans.len(), 2);
+        assert_eq!(rs.spans[0].style, Style::new().fg(Color::Red).bold());
+        assert_eq!(rs.spans[1].text, "y");
+        assert_eq!(rs.spans[1].style, Style::default());
+    }
+
+    #[test]
+    fn test_close_tag_aliases() {
+        let rs = RichString::parse("<b><i>x</bold>y</I>");
+        assert_eq!(rs.spans[1].style, Style::default());
+
+        let rs = RichString::parse("<fg=red><u=green>x</color>y");
+        assert_eq!(rs.spans[1].style, Style::default());
+
+        let rs = RichString::parse("<bg_red>a</bg>b");
+        assert_eq!(rs.spans[1], Span::new("b", Style::default()));
+
+        let rs = RichString::parse("<red>a</color>b");
+        assert_eq!(rs.spans[1], Span::new("b", Style::default()));
+
+        let rs = RichString::parse("<bg=blue>a</bg_blue>b");
+        assert_eq!(rs.spans[1], Span::new("b", Style::default()));
+    }
+
+    #[test]
+    fn test_close_tag_empty_closes_top() {
+        let rs = RichString::parse("<red><b>x</>y</>z");
+        assert_eq!(rs.spans[1].style, Style::new().fg(Color::Red));
+        assert_eq!(rs.spans[2].style, Style::default());
+    }
+
+    #[test]
+    fn test_close_policy_pop() {
+        let options = ParseOptions::new().close_policy(ClosePolicy::Pop);
+        let rs = RichString::parse_with_options("<red><b>x</red>y</b>", &options).unwrap();
+        assert_eq!(rs.spans[1].style, Style::new().fg(Color::Red));
+    }
+
+    #[test]
+    fn test_close_policy_error() {
+        let options = ParseOptions::new().close_policy(ClosePolicy::Error);
+        let err = RichString::parse_with_options("<red><b>x</red>y</b>", &options).unwrap_err();
+        assert_eq!(err, ParseError::MismatchedClose {
+            expected: "b".to_string(),
+            found: "red".to_string(),
+            span: 9..15,
+        });
+
+        let err = RichString::parse_with_options("<red>x</b>", &options).unwrap_err();
+        assert_eq!(err, ParseError::UnbalancedClose { tag: "b".to_string(), span: 6..10 });
+
+        // Unknown tags are still ignored outside strict mode
+        assert!(RichString::parse_with_options("<bodl>x</bodl>", &options).is_ok());
+    }
+
+    #[test]
+    fn test_parse_with_registry() {
+        let mut registry = TagRegistry::new();
+        registry
+            .register("Warning", Style::new().fg(Color::Yellow).bold())
+            .register_fn("path", |value, style| style.underline().url(value.unwrap_or("file:///")));
+
+        let rs = RichString::parse_with(&registry, "<warning>See <path=file:///tmp>tmp</path></warning> <red>x</red>");
+        assert_eq!(rs.spans[0].style, Style::new().fg(Color::Yellow).bold());
+        assert_eq!(rs.spans[1].style, Style::new().fg(Color::Yellow).bold().underline().url("file:///tmp"));
+        assert_eq!(rs.spans[3].style.fg, Color::Red);
+    }
+
+    #[test]
+    fn test_registry_overrides_builtin() {
+        let mut registry = TagRegistry::new();
+        registry.register("red", Style::new().fg(Color::Rgb(200, 0, 0)));
+        let rs = RichString::parse_with(&registry, "<red>x</red>");
+        assert_eq!(rs.spans[0].style.fg, Color::Rgb(200, 0, 0));
+    }
+
+    #[test]
+    fn test_registry_strict() {
+        let mut registry = TagRegistry::new();
+        registry.register("error", Style::new().fg(Color::Red));
+        let options = ParseOptions::new().strict().registry(&registry);
+        assert!(RichString::parse_with_options("<error>x</error>", &options).is_ok());
+        assert!(RichString::try_parse("<error>x</error>").is_err());
+    }
+
+    #[test]
+    fn test_parse_style_expression() {
+        let style = parse_style("bold red on #202020 underline").unwrap();
+        assert_eq!(style, Style::new().bold().fg(Color::Red).bg(Color::Rgb(0x20, 0x20, 0x20)).underline());
+
+        let style = parse_style("  Bright Blue on bright_black i ").unwrap();
+        assert_eq!(style, Style::new().fg(Color::BrightBlue).bg(Color::BrightBlack).italic());
+    }
+
+    #[test]
+    fn test_parse_style_expression_errors() {
+        let err = parse_style("bold redd").unwrap_err();
+        assert_eq!(err, ParseError::UnknownStyle { word: "redd".to_string(), span: 5..9 });
+
+        let err = parse_style("red on nope").unwrap_err();
+        assert_eq!(err, ParseError::InvalidColor { value: "nope".to_string(), span: 7..11 });
+
+        assert!(parse_style("red on").is_err());
+    }
+
+    #[test]
+    fn test_parse_style_tag() {
+        let rs = RichString::parse("<style=\"bold red on blue\">x</style>y");
+        assert_eq!(rs.spans[0].style, Style::new().bold().fg(Color::Red).bg(Color::Blue));
+        assert_eq!(rs.spans[1].style, Style::default());
+
+        let rs = RichString::parse("<i><style='u=green link=https://example.com/?a=b'>x</style></i>");
+        assert_eq!(rs.spans[0].style, Style::new().italic().underline().underline_color(Color::Green).url("https://example.com/?a=b"));
+    }
+
+    #[test]
+    fn test_parse_style_tag_errors() {
+        let err = RichString::try_parse("ab<style=\"bold bodl\">x</style>").unwrap_err();
+        assert_eq!(err, ParseError::UnknownStyle { word: "bodl".to_string(), span: 15..19 });
+
+        let err = RichString::try_parse("<style=\"u=nope\">x</style>").unwrap_err();
+        assert_eq!(err, ParseError::InvalidColor { value: "nope".to_string(), span: 10..14 });
+    }
+
+    #[test]
+    fn test_style_from_str() {
+        let style: Style = "dim cu=#00ff00 fg=123 link=https://example.com".parse().unwrap();
+        assert_eq!(style, Style::new().dim().curly_underline().underline_color(Color::Rgb(0, 255, 0)).fg(Color::Ansi256(123)).url("https://example.com"));
+
+        let err = "bold x=1".parse::<Style>().unwrap_err();
+        assert_eq!(err, ParseError::UnknownStyle { word: "x=1".to_string(), span: 5..8 });
+    }
+
+    #[test]
+    fn test_parse_multiple_attributes() {
+        let rs = RichString::parse("<color=red bg=blue bold>x</color>y");
+        assert_eq!(rs.spans[0].style, Style::new().fg(Color::Red).bg(Color::Blue).bold());
+        assert_eq!(rs.spans[1].style, Style::default());
+
+        let rs = RichString::parse("<u=green i fg=bright red>x</u>");
+        assert_eq!(rs.spans[0].style, Style::new().underline().underline_color(Color::Green).italic().fg(Color::BrightRed));
+    }
+
+    #[test]
+    fn test_parse_quoted_values() {
+        let rs = RichString::parse("<link=https://x.com/?a=b&c=d>x</link>");
+        assert_eq!(rs.spans[0].style.url.as_deref(), Some("https://x.com/?a=b&c=d"));
+
+        let rs = RichString::parse("<link=\"https://x.com/a b?q=1>2\" b>x</link>");
+        assert_eq!(rs.spans[0].style, Style::new().url("https://x.com/a b?q=1>2").bold());
+        assert_eq!(rs.spans[0].text, "x");
+
+        let rs = RichString::parse(r#"<link='it\'s \\ \"here\"'>x</link>"#);
+        assert_eq!(rs.spans[0].style.url.as_deref(), Some(r#"it's \ "here""#));
+    }
+
+    #[test]
+    fn test_parse_unterminated_quote() {
+        // An unterminated quote does not swallow the rest of the input
+        let rs = RichString::parse("<link=\"oops>x</link> y");
+        assert_eq!(rs.spans[0].text, "x");
+        assert_eq!(rs.spans[1].style, Style::default());
+    }
+
+    #[test]
+    fn test_parse_escaped_value_spans() {
+        // Spans cover the value as written, escapes included
+        let input = r#"<b bg="no\"pe">x</b>"#;
+        let err = RichString::try_parse(input).unwrap_err();
+        assert_eq!(err, ParseError::InvalidColor { value: "no\"pe".to_string(), span: 7..13 });
+        assert_eq!(&input[err.span()], r#"no\"pe"#);
+
+        let input = r#"<style="link=\\x bodl">x</style>"#;
+        let err = RichString::try_parse(input).unwrap_err();
+        assert!(matches!(err, ParseError::UnknownStyle { ref word, .. } if word == "bodl"));
+        assert_eq!(&input[err.span()], "bodl");
+    }
+
+    #[test]
+    fn test_parse_multiple_attributes_errors() {
+        let err = RichString::try_parse("<red bodl>x</red>").unwrap_err();
+        assert_eq!(err, ParseError::UnknownTag { tag: "bodl".to_string(), span: 0..10 });
+
+        let err = RichString::try_parse("<b bg=\"nope\">x</b>").unwrap_err();
+        assert_eq!(err, ParseError::InvalidColor { value: "nope".to_string(), span: 7..11 });
+
+        // Lenient parsing still applies the valid attributes
+        let rs = RichString::parse("<bodl red>x</bodl>");
+        assert_eq!(rs.spans[0].style.fg, Color::Red);
+    }
+
+    #[test]
+    fn test_parse_inline_elements() {
+        let rs = RichString::parse("<red>a<br>b<TAB/>c<nbsp/>d</red>");
+        assert_eq!(rs.plain().to_string(), "a\nb\tc\u{a0}d");
+        assert!(rs.spans.iter().all(|span| span.style == Style::new().fg(Color::Red)));
+
+        let rs = RichString::try_parse("<b>x<hr/>y</b>").unwrap();
+        assert_eq!(rs.spans[1], Span::rule(Style::new().bold()));
+        assert_eq!(rs.spans[2].style, Style::new().bold());
+        assert_eq!(rs.plain().to_string(), format!("x{}y", "─".repeat(80)));
+
+        // Registered tags take precedence over the built-in elements
+        let mut registry = TagRegistry::new();
+        registry.register("hr", Style::new().italic());
+        let rs = RichString::parse_with(&registry, "<hr>x</hr>");
+        assert_eq!(rs.spans, vec![Span::new("x", Style::new().italic())]);
+    }
+
+    #[test]
+    fn test_parse_self_closing() {
+        let rs = RichString::try_parse("<b/>a<red />b").unwrap();
+        assert_eq!(rs.spans.iter().map(|span| &span.style).collect::<Vec<_>>(), [&Style::default(); 2]);
+        assert_eq!(RichString::try_parse("<bodl/>").unwrap_err(), ParseError::UnknownTag { tag: "bodl".to_string(), span: 0..7 });
+
+        // A slash ending an unquoted value belongs to the value
+        let rs = RichString::parse("<link=https://x.dev/>x</link>");
+        assert_eq!(rs.spans[0].style.url.as_deref(), Some("https://x.dev/"));
+        let rs = RichString::parse("<link=\"https://x.dev\"/>x");
+        assert_eq!(rs.spans[0].style, Style::default());
+    }
+
+    #[test]
+    fn test_width() {
+        let rs = RichString::parse("<b>ab</b>中\n<red>e\u{301}</red>");
+        assert_eq!(rs.width(), 4);
+        assert_eq!(rs.spans[0].width(), 2);
+        assert_eq!(RichString::parse("a<red>b\nabc</red>d").width(), 4);
+
+        // A rule is as wide as it is displayed
+        let rs = RichString::parse("a<hr/>");
+        assert_eq!(rs.width(), 81);
+        assert_eq!(rs.width(), rs.plain().to_string().chars().count());
+        assert_eq!(format!("{:>82}", rs).matches(' ').count(), 1);
+    }
+
+    #[test]
+    fn test_display_alignment() {
+        let rs = RichString::parse("<b>ab</b>c");
+        assert_eq!(format!("{}", rs), "\x1b[1mab\x1b[22mc");
+        assert_eq!(format!("{:5}|", rs), "\x1b[1mab\x1b[22mc  |");
+        assert_eq!(format!("{:>5}|", rs), "  \x1b[1mab\x1b[22mc|");
+        assert_eq!(format!("{:*^6}|", rs), "*\x1b[1mab\x1b[22mc**|");
+        assert_eq!(format!("{:2}|", rs), "\x1b[1mab\x1b[22mc|");
+        assert_eq!(format!("{:.2}|", rs), "\x1b[1mab\x1b[0m|");
+        assert_eq!(format!("{:>4.1}|", rs), "   \x1b[1ma\x1b[0m|");
+        assert_eq!(format!("{:>w$}|", rs, w = 4), " \x1b[1mab\x1b[22mc|");
+    }
+
+    #[test]
+    fn test_padding() {
+        let rs = RichString::parse("<red>中</red>");
+        assert_eq!(rs.pad_left(4).plain().to_string(), "  中");
+        assert_eq!(rs.pad_right(3).plain().to_string(), "中 ");
+        assert_eq!(rs.center(5).plain().to_string(), " 中  ");
+        assert_eq!(rs.pad_left(1), rs);
+        assert_eq!(rs.pad_left(4).spans[0].style, Style::default());
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::style::Style;
+
+type TagFn = dyn for<'s> Fn(Option<&'s str>, Style<'s>) -> Style<'s> + Send + Sync;
+
+enum TagHandler {
+    Style(Style<'static>),
+    Fn(Box<TagFn>),
+}
+
+/// A set of user-defined tags, consulted before the built-in ones.
+///
+/// Tag names are case-insensitive. Registering a built-in name such as `red`
+/// overrides the built-in tag.
+///
+/// ```rust
+/// use richparse::{Color, RichString, Style, TagRegistry};
+///
+/// let mut registry = TagRegistry::new();
+/// registry
+///     .register("error", Style::new().fg(Color::Red).bold())
+///     .register_fn("kbd", |_, style| style.inverse());
+///
+/// let rs = RichString::parse_with(&registry, "<error>Press <kbd>q</kbd></error>");
+/// assert_eq!(rs.spans[1].style, Style::new().fg(Color::Red).bold().inverse());
+/// ```
+#[derive(Default)]
+pub struct TagRegistry {
+    tags: HashMap<String, TagHandler>,
+}
+
+impl TagRegistry {
+    /// Creates an empty registry. The built-in tags always apply to names it
+    /// does not contain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a tag that applies `style` on top of the current style.
+    pub fn register<S: Into<String>>(&mut self, name: S, style: Style<'static>) -> &mut Self {
+        self.tags.insert(name.into().to_lowercase(), TagHandler::Style(style));
+        self
+    }
+
+    /// Registers a tag computed by `f` from the tag's value (`<name=value>`)
+    /// and the current style.
+    pub fn register_fn<S, F>(&mut self, name: S, f: F) -> &mut Self
+    where
+        S: Into<String>,
+        F: for<'s> Fn(Option<&'s str>, Style<'s>) -> Style<'s> + Send + Sync + 'static,
+    {
+        self.tags.insert(name.into().to_lowercase(), TagHandler::Fn(Box::new(f)));
+        self
+    }
+
+    /// Returns `true` if a custom tag with this name has been registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.tags.contains_key(&name.to_lowercase())
+    }
+
+    /// Applies the custom tag `name` to `style`, returning `false` if no such tag is registered.
+    pub(crate) fn apply<'a>(&self, name: &str, value: Option<&'a str>, style: &mut Style<'a>) -> bool {
+        let Some(handler) = self.tags.get(name) else {
+            return false;
+        };
+        let current = std::mem::take(style);
+        *style = match handler {
+            TagHandler::Style(custom) => current.merge(custom),
+            TagHandler::Fn(f) => f(value, current),
+        };
+        true
+    }
+}
+
+impl fmt::Debug for TagRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&str> = self.tags.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        f.debug_struct("TagRegistry").field("tags", &names).finish()
+    }
+}
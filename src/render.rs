@@ -0,0 +1,598 @@
+use std::fmt;
+use std::io::IsTerminal;
+use crate::parser::RichString;
+use crate::sanitize::{is_safe_url, sanitize};
+use crate::span::Span;
+use crate::style::Style;
+
+/// How many colors the output can display.
+///
+/// Variants are ordered from fewest to most colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum ColorDepth {
+    /// No colors at all; modifiers such as bold are still emitted.
+    None,
+    /// The 16 basic colors. Underline colors are not shown.
+    Ansi16,
+    /// The xterm 256-color palette.
+    Ansi256,
+    /// 24-bit RGB colors.
+    #[default]
+    TrueColor,
+}
+
+/// Options controlling how a [`RichString`](crate::RichString) is rendered.
+///
+/// The default emits everything: true color, hyperlinks and underline styles.
+/// Use [`RenderOptions::detect`] to match what the terminal supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// Whether any escape sequences are written; when `false` only the text is emitted.
+    pub styled: bool,
+    pub color_depth: ColorDepth,
+    /// Whether hyperlinks are emitted as OSC 8 sequences.
+    pub hyperlinks: bool,
+    /// Whether double and curly underlines and underline colors are emitted;
+    /// when `false` they fall back to a plain underline.
+    pub underline_styles: bool,
+    /// The width of the output in columns, which horizontal rules (`<hr/>`) fill.
+    pub width: usize,
+    /// Whether span text and link URLs are treated as untrusted; see
+    /// [`RenderOptions::sanitize`].
+    pub sanitize: bool,
+}
+
+/// The output width assumed when none is known.
+pub(crate) const DEFAULT_WIDTH: usize = 80;
+
+/// Returns a horizontal rule `width` columns wide.
+pub(crate) fn rule(width: usize) -> String {
+    "─".repeat(width)
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            styled: true,
+            color_depth: ColorDepth::default(),
+            hyperlinks: true,
+            underline_styles: true,
+            width: DEFAULT_WIDTH,
+            sanitize: false,
+        }
+    }
+}
+
+impl RenderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Options that write the text without any escape sequences.
+    pub fn plain() -> Self {
+        Self {
+            styled: false,
+            color_depth: ColorDepth::None,
+            hyperlinks: false,
+            underline_styles: false,
+            width: DEFAULT_WIDTH,
+            sanitize: false,
+        }
+    }
+
+    pub fn color_depth(mut self, depth: ColorDepth) -> Self {
+        self.color_depth = depth;
+        self
+    }
+
+    pub fn hyperlinks(mut self, enabled: bool) -> Self {
+        self.hyperlinks = enabled;
+        self
+    }
+
+    pub fn underline_styles(mut self, enabled: bool) -> Self {
+        self.underline_styles = enabled;
+        self
+    }
+
+    /// Sets the number of columns rules are drawn across, 80 by default.
+    ///
+    /// Only rendering uses it: [`RichString::width`] still counts a rule as 80 columns.
+    pub fn width(mut self, columns: usize) -> Self {
+        self.width = columns;
+        self
+    }
+
+    /// Renders text that may come from an untrusted source safely.
+    ///
+    /// Escape sequences in span text are removed and other control characters
+    /// are made visible, as by [`sanitize`](crate::sanitize), so the text cannot
+    /// retitle the window, clear the screen or move the cursor. Links are only
+    /// emitted for `http`, `https`, `mailto` and `file` URLs without control
+    /// characters; the text of any other link is written without it.
+    pub fn sanitize(mut self, enabled: bool) -> Self {
+        self.sanitize = enabled;
+        self
+    }
+
+    /// Detects what standard output supports; see [`RenderOptions::detect_for`].
+    pub fn detect() -> Self {
+        Self::detect_for(&std::io::stdout())
+    }
+
+    /// Detects what `stream` supports from whether it is a terminal and from the environment.
+    ///
+    /// * `CLICOLOR_FORCE` or `FORCE_COLOR` (other than `0`) enable styling even when
+    ///   `stream` is not a terminal. `FORCE_COLOR=1`, `2` and `3` select 16, 256 and
+    ///   true color respectively.
+    /// * Otherwise styling is disabled when `stream` is not a terminal, when
+    ///   `CLICOLOR=0`, or when `TERM=dumb`.
+    /// * `NO_COLOR` (non-empty) disables colors but keeps modifiers such as bold.
+    /// * The color depth comes from `COLORTERM` (`truecolor`/`24bit`) and `TERM`
+    ///   (`*-256color`), defaulting to 16 colors.
+    /// * Hyperlinks and underline styles are enabled for terminals known to support
+    ///   them; `FORCE_HYPERLINK` overrides the hyperlink choice.
+    /// * The width comes from `COLUMNS`, defaulting to 80.
+    pub fn detect_for<S: IsTerminal>(stream: &S) -> Self {
+        Self::from_env(|name| std::env::var(name).ok(), stream.is_terminal())
+    }
+
+    fn from_env<F: Fn(&str) -> Option<String>>(env: F, is_terminal: bool) -> Self {
+        let set = |name: &str| env(name).filter(|value| !value.is_empty());
+        let term = env("TERM").unwrap_or_default().to_lowercase();
+        let term_program = env("TERM_PROGRAM").unwrap_or_default();
+        let vte_version = env("VTE_VERSION").and_then(|v| v.parse::<u32>().ok()).unwrap_or(0);
+
+        let force_color = set("FORCE_COLOR").filter(|v| v != "0" && v != "false");
+        let forced = force_color.is_some() || set("CLICOLOR_FORCE").is_some_and(|v| v != "0");
+        let disabled = set("FORCE_COLOR").is_some_and(|v| v == "0" || v == "false")
+            || !is_terminal
+            || set("CLICOLOR").is_some_and(|v| v == "0")
+            || term == "dumb";
+
+        let width = env("COLUMNS")
+            .and_then(|columns| columns.trim().parse().ok())
+            .filter(|&columns| columns > 0)
+            .unwrap_or(DEFAULT_WIDTH);
+
+        if !forced && disabled {
+            return Self::plain().width(width);
+        }
+
+        let detected_depth = if set("COLORTERM").is_some_and(|v| v == "truecolor" || v == "24bit")
+            || term.contains("truecolor")
+            || term.contains("direct")
+            || set("WT_SESSION").is_some()
+        {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        };
+        let color_depth = match force_color.as_deref() {
+            _ if set("NO_COLOR").is_some() && !forced => ColorDepth::None,
+            Some("1") => ColorDepth::Ansi16,
+            Some("2") => ColorDepth::Ansi256,
+            Some("3") => ColorDepth::TrueColor,
+            _ => detected_depth,
+        };
+
+        // Terminals known to handle OSC 8 hyperlinks and extended underlines
+        let modern = matches!(term_program.as_str(), "iTerm.app" | "WezTerm" | "vscode" | "ghostty")
+            || ["kitty", "wezterm", "foot", "alacritty", "ghostty"].iter().any(|t| term.contains(t))
+            || vte_version >= 5000
+            || set("WT_SESSION").is_some()
+            || set("KONSOLE_VERSION").is_some();
+
+        let hyperlinks = match set("FORCE_HYPERLINK").as_deref() {
+            Some("0") => false,
+            Some(_) => true,
+            None => modern && is_terminal,
+        };
+
+        Self {
+            styled: true,
+            color_depth,
+            hyperlinks,
+            underline_styles: modern,
+            width,
+            sanitize: false,
+        }
+    }
+
+    /// Returns `style` adjusted to what these options allow.
+    pub(crate) fn adapt<'a>(&self, style: &Style<'a>) -> Style<'a> {
+        if !self.styled {
+            return Style::default();
+        }
+        let mut style = style.clone();
+        style.fg = style.fg.downgrade(self.color_depth);
+        style.bg = style.bg.downgrade(self.color_depth);
+        // Underline colors only exist as indexed (58;5) and RGB (58;2) colors
+        style.underline_color = match self.color_depth {
+            ColorDepth::None | ColorDepth::Ansi16 => None,
+            depth => style.underline_color.map(|color| color.downgrade(depth)),
+        };
+        if !self.hyperlinks || (self.sanitize && style.url.as_deref().is_some_and(|url| !is_safe_url(url))) {
+            style.url = None;
+        }
+        if !self.underline_styles {
+            style.underline |= style.double_underline || style.curly_underline;
+            style.double_underline = false;
+            style.curly_underline = false;
+            style.underline_color = None;
+        }
+        style
+    }
+}
+
+/// Renders spans to ANSI escape sequences, emitting only what changes between them.
+///
+/// The renderer remembers the style the terminal is currently in. For each span
+/// it writes a single `CSI ... m` sequence containing just the SGR parameters that
+/// differ from the previous span, and resets the terminal once in
+/// [`AnsiRenderer::finish`]. Because the state survives between calls, spans can
+/// be rendered as they arrive.
+///
+/// ```rust
+/// use richparse::{AnsiRenderer, RichString};
+///
+/// let rs = RichString::parse("<red>a<b>b</b></red>c");
+/// let mut out = String::new();
+/// let mut renderer = AnsiRenderer::new();
+/// for span in &rs.spans {
+///     renderer.write_span(&mut out, span).unwrap();
+/// }
+/// renderer.finish(&mut out).unwrap();
+/// assert_eq!(out, "\x1b[31ma\x1b[1mb\x1b[39;22mc");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AnsiRenderer {
+    options: RenderOptions,
+    current: Style<'static>,
+}
+
+impl AnsiRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: RenderOptions) -> Self {
+        Self {
+            options,
+            current: Style::default(),
+        }
+    }
+
+    /// Writes `span`, preceded by whatever escape sequences are needed to switch
+    /// from the previous span's style to its style.
+    pub fn write_span<W: fmt::Write>(&mut self, out: &mut W, span: &Span<'_>) -> fmt::Result {
+        if span.text.is_empty() && !span.is_rule() {
+            return Ok(());
+        }
+
+        let style = &self.options.adapt(&span.style);
+        if style.url != self.current.url {
+            // Hyperlink end
+            if self.current.url.is_some() {
+                out.write_str("\x1b]8;;\x1b\\")?;
+            }
+            // Hyperlink start
+            if let Some(url) = &style.url {
+                write!(out, "\x1b]8;;{}\x1b\\", url)?;
+            }
+        }
+
+        let params = sgr_diff(&self.current, style);
+        if !params.is_empty() {
+            write!(out, "\x1b[{}m", params.join(";"))?;
+        }
+        if *style != self.current {
+            self.current = style.clone().into_owned();
+        }
+
+        if span.is_rule() {
+            out.write_str(&rule(self.options.width))
+        } else if self.options.sanitize {
+            out.write_str(&sanitize(&span.text))
+        } else {
+            out.write_str(&span.text)
+        }
+    }
+
+    /// Closes any open hyperlink and resets the terminal if a style is active.
+    pub fn finish<W: fmt::Write>(&mut self, out: &mut W) -> fmt::Result {
+        if self.current.url.is_some() {
+            out.write_str("\x1b]8;;\x1b\\")?;
+        }
+        if self.current != Style::default() {
+            out.write_str("\x1b[0m")?;
+        }
+        self.current = Style::default();
+        Ok(())
+    }
+}
+
+/// Returns the SGR parameters that switch the terminal from `from` to `to`.
+fn sgr_diff(from: &Style<'_>, to: &Style<'_>) -> Vec<String> {
+    let mut params = Vec::new();
+
+    if to.fg != from.fg {
+        params.push(to.fg.sgr_fg());
+    }
+    if to.bg != from.bg {
+        params.push(to.bg.sgr_bg());
+    }
+    if to.underline_color != from.underline_color {
+        match &to.underline_color {
+            Some(color) => params.push(color.sgr_underline_color()),
+            None => params.push("59".to_string()),
+        }
+    }
+
+    // Bold and dim share a single reset code
+    let reset_intensity = (from.bold && !to.bold) || (from.dim && !to.dim);
+    if reset_intensity {
+        params.push("22".to_string());
+    }
+    if to.bold && (reset_intensity || !from.bold) {
+        params.push("1".to_string());
+    }
+    if to.dim && (reset_intensity || !from.dim) {
+        params.push("2".to_string());
+    }
+
+    let underline = underline_param(to);
+    if underline != underline_param(from) {
+        params.push(underline.unwrap_or("24").to_string());
+    }
+
+    let toggles = [
+        (from.italic, to.italic, "3", "23"),
+        (from.blink, to.blink, "5", "25"),
+        (from.inverse, to.inverse, "7", "27"),
+        (from.hidden, to.hidden, "8", "28"),
+        (from.strikethrough, to.strikethrough, "9", "29"),
+        (from.overline, to.overline, "53", "55"),
+    ];
+    for (was, is, on, off) in toggles {
+        if was != is {
+            params.push(if is { on } else { off }.to_string());
+        }
+    }
+
+    params
+}
+
+/// The SGR parameter for the underline a style displays; a curly underline wins
+/// over a double one, which wins over a single one.
+fn underline_param(style: &Style<'_>) -> Option<&'static str> {
+    if style.curly_underline {
+        Some("4:3")
+    } else if style.double_underline {
+        Some("4:2")
+    } else if style.underline {
+        Some("4")
+    } else {
+        None
+    }
+}
+
+/// Displays the text of a [`RichString`] without any styling, as returned by
+/// [`RichString::plain`]. Rules are written 80 columns wide.
+///
+/// ```rust
+/// use richparse::RichString;
+///
+/// let rs = RichString::parse("<bold>See</bold> <link=https://example.com>the docs</link>");
+/// assert_eq!(rs.plain().to_string(), "See the docs");
+/// assert_eq!(rs.plain().with_urls().to_string(), "See the docs (https://example.com)");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct PlainText<'r, 'a> {
+    rich: &'r RichString<'a>,
+    urls: bool,
+}
+
+impl<'r, 'a> PlainText<'r, 'a> {
+    pub(crate) fn new(rich: &'r RichString<'a>) -> Self {
+        Self { rich, urls: false }
+    }
+
+    /// Appends the target of each hyperlink after its text, as `text (url)`.
+    ///
+    /// Links whose text is the URL itself are left as they are.
+    pub fn with_urls(mut self) -> Self {
+        self.urls = true;
+        self
+    }
+}
+
+impl fmt::Display for PlainText<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let spans = &self.rich.spans;
+        let mut link_text = String::new();
+        for (i, span) in spans.iter().enumerate() {
+            if span.is_rule() {
+                f.write_str(&rule(DEFAULT_WIDTH))?;
+            } else {
+                f.write_str(&span.text)?;
+            }
+            if !self.urls {
+                continue;
+            }
+            let Some(url) = span.style.url.as_deref() else {
+                continue;
+            };
+            // A link can span several styled spans; write the URL after the last one
+            link_text.push_str(&span.text);
+            let next_url = spans.get(i + 1).and_then(|next| next.style.url.as_deref());
+            if next_url != Some(url) {
+                if link_text != url {
+                    write!(f, " ({})", url)?;
+                }
+                link_text.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::Color;
+
+    fn render(markup: &str) -> String {
+        RichString::parse(markup).to_string()
+    }
+
+    #[test]
+    fn test_render_merges_parameters() {
+        assert_eq!(render("<red bold u>x</red>"), "\x1b[31;1;4mx\x1b[0m");
+    }
+
+    #[test]
+    fn test_render_only_diffs() {
+        assert_eq!(render("<red>a<i>b</i>c</red>"), "\x1b[31ma\x1b[3mb\x1b[23mc\x1b[0m");
+        assert_eq!(render("<bg=blue>a</bg><bg=blue>b</bg>"), "\x1b[44mab\x1b[0m");
+    }
+
+    #[test]
+    fn test_render_default_style_is_plain() {
+        assert_eq!(render("plain text"), "plain text");
+        assert_eq!(render("<b>a</b>b"), "\x1b[1ma\x1b[22mb");
+    }
+
+    #[test]
+    fn test_render_intensity_reset() {
+        // 22 turns off both bold and dim, so bold must be re-enabled
+        assert_eq!(render("<b><dim>a</dim>b</b>"), "\x1b[1;2ma\x1b[22;1mb\x1b[0m");
+    }
+
+    #[test]
+    fn test_render_underline_kinds() {
+        assert_eq!(render("<u>a<cu=red>b</cu>c</u>"), "\x1b[4ma\x1b[58;5;1;4:3mb\x1b[59;4mc\x1b[0m");
+    }
+
+    #[test]
+    fn test_render_hyperlinks() {
+        assert_eq!(
+            render("<link=a>x</link><link=b>y</link>z"),
+            "\x1b]8;;a\x1b\\x\x1b]8;;\x1b\\\x1b]8;;b\x1b\\y\x1b]8;;\x1b\\z"
+        );
+    }
+
+    #[test]
+    fn test_render_color_depth() {
+        let rs = RichString::parse("<fg=#ff8700 bg=196 u=#0000ee b>x</fg>");
+        let render = |depth| rs.render(&RenderOptions::new().color_depth(depth));
+
+        assert_eq!(render(ColorDepth::TrueColor), "\x1b[38;2;255;135;0;48;5;196;58;2;0;0;238;1;4mx\x1b[0m");
+        assert_eq!(render(ColorDepth::Ansi256), "\x1b[38;5;208;48;5;196;58;5;21;1;4mx\x1b[0m");
+        assert_eq!(render(ColorDepth::Ansi16), "\x1b[33;101;1;4mx\x1b[0m");
+        assert_eq!(render(ColorDepth::None), "\x1b[1;4mx\x1b[0m");
+    }
+
+    #[test]
+    fn test_render_no_color_plain_text() {
+        let rs = RichString::parse("<red>a</red><bg=blue>b</bg>");
+        assert_eq!(rs.render(&RenderOptions::new().color_depth(ColorDepth::None)), "ab");
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let rs = RichString::parse("<red>a<<b</red> <link=https://x.dev>c <b>d</b></link>");
+        assert_eq!(rs.plain().to_string(), "a<b c d");
+        assert_eq!(rs.plain().with_urls().to_string(), "a<b c d (https://x.dev)");
+
+        let rs = RichString::parse("<link=https://x.dev>https://x.dev</link>, <link=https://y.dev>y</link>");
+        assert_eq!(rs.plain().with_urls().to_string(), "https://x.dev, y (https://y.dev)");
+    }
+
+    #[test]
+    fn test_render_plain_and_fallbacks() {
+        let rs = RichString::parse("<red cu=blue link=https://example.com>x</red>");
+        assert_eq!(rs.render(&RenderOptions::plain()), "x");
+
+        let options = RenderOptions::new().hyperlinks(false).underline_styles(false);
+        assert_eq!(rs.render(&options), "\x1b[31;4mx\x1b[0m");
+    }
+
+    fn detect(vars: &[(&str, &str)], is_terminal: bool) -> RenderOptions {
+        RenderOptions::from_env(
+            |name| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string()),
+            is_terminal,
+        )
+    }
+
+    #[test]
+    fn test_detect_terminal() {
+        assert_eq!(detect(&[("TERM", "xterm")], true).color_depth, ColorDepth::Ansi16);
+        assert_eq!(detect(&[("TERM", "xterm-256color")], true).color_depth, ColorDepth::Ansi256);
+        assert_eq!(detect(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")], true).color_depth, ColorDepth::TrueColor);
+
+        let kitty = detect(&[("TERM", "xterm-kitty"), ("COLORTERM", "truecolor")], true);
+        assert!(kitty.hyperlinks && kitty.underline_styles);
+
+        let unknown = detect(&[("TERM", "xterm")], true);
+        assert!(!unknown.hyperlinks && !unknown.underline_styles);
+    }
+
+    #[test]
+    fn test_detect_disabled() {
+        assert_eq!(detect(&[("TERM", "xterm-256color")], false), RenderOptions::plain());
+        assert_eq!(detect(&[("TERM", "dumb")], true), RenderOptions::plain());
+        assert_eq!(detect(&[("TERM", "xterm"), ("CLICOLOR", "0")], true), RenderOptions::plain());
+
+        let no_color = detect(&[("TERM", "xterm-256color"), ("NO_COLOR", "1")], true);
+        assert!(no_color.styled);
+        assert_eq!(no_color.color_depth, ColorDepth::None);
+    }
+
+    #[test]
+    fn test_detect_forced() {
+        let forced = detect(&[("CLICOLOR_FORCE", "1"), ("TERM", "xterm-256color")], false);
+        assert!(forced.styled);
+        assert_eq!(forced.color_depth, ColorDepth::Ansi256);
+        assert!(!forced.hyperlinks);
+
+        assert_eq!(detect(&[("FORCE_COLOR", "3")], false).color_depth, ColorDepth::TrueColor);
+        assert_eq!(detect(&[("FORCE_COLOR", "0"), ("TERM", "xterm")], true), RenderOptions::plain());
+        assert!(detect(&[("FORCE_HYPERLINK", "1"), ("TERM", "xterm")], true).hyperlinks);
+    }
+
+    #[test]
+    fn test_render_streaming() {
+        let mut out = String::new();
+        let mut renderer = AnsiRenderer::new();
+        renderer.write_span(&mut out, &Span::new("a", Style::new().fg(Color::Green))).unwrap();
+        renderer.write_span(&mut out, &Span::new("", Style::new().bold())).unwrap();
+        renderer.write_span(&mut out, &Span::new("b", Style::new().fg(Color::Green))).unwrap();
+        renderer.finish(&mut out).unwrap();
+        assert_eq!(out, "\x1b[32mab\x1b[0m");
+    }
+
+    #[test]
+    fn test_render_rule() {
+        let rs = RichString::parse("<red>a<hr/></red>");
+        assert_eq!(rs.render(&RenderOptions::new().width(4)), "\x1b[31ma────\x1b[0m");
+        assert_eq!(rs.render(&RenderOptions::plain().width(2)), "a──");
+        assert_eq!(detect(&[("COLUMNS", "120")], false).width, 120);
+        assert_eq!(detect(&[("COLUMNS", "wide")], true).width, 80);
+    }
+
+    #[test]
+    fn test_render_sanitize() {
+        let rs = RichString::new(vec![
+            Span::new("a\x1b[2J\x07", Style::new().bold()),
+            Span::new("b", Style::new().url("javascript:alert(1)")),
+            Span::new("c", Style::new().url("https://x.dev")),
+        ]);
+        let options = RenderOptions::new().sanitize(true);
+        assert_eq!(rs.render(&options), "\x1b[1ma␇\x1b[22mb\x1b]8;;https://x.dev\x1b\\c\x1b]8;;\x1b\\\x1b[0m");
+        assert!(rs.render(&RenderOptions::new()).contains("\x1b[2J"));
+        assert_eq!(rs.render(&RenderOptions::plain().sanitize(true)), "a␇bc");
+    }
+}
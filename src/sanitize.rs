@@ -0,0 +1,85 @@
+//! Neutralizing control characters and escape sequences in untrusted text.
+
+use std::borrow::Cow;
+use crate::ansi::escape_len;
+
+/// URL schemes that are let through as hyperlinks by [`RenderOptions::sanitize`](crate::RenderOptions::sanitize)
+/// and by the HTML and SVG exports.
+const URL_SCHEMES: [&str; 4] = ["http", "https", "mailto", "file"];
+
+/// Makes `text` safe to write to a terminal.
+///
+/// Escape sequences such as `ESC ] 0 ; title BEL` or `ESC [ 2 J` are removed
+/// entirely. Any other control character is replaced with a visible stand-in:
+/// C0 controls and DEL with their Unicode control pictures (`␛`, `␇`, `␍`, ...)
+/// and C1 controls with `�`. Newlines and tabs are kept.
+///
+/// ```rust
+/// use richparse::sanitize;
+///
+/// assert_eq!(sanitize("a\x1b]0;pwned\x07b\x1b[2Jc"), "abc");
+/// assert_eq!(sanitize("50%\r100%\x08"), "50%␍100%␈");
+/// assert_eq!(sanitize("plain\ttext\n"), "plain\ttext\n");
+/// ```
+pub fn sanitize(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(is_unsafe) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '\x1b' {
+            rest = &rest[escape_len(rest)..];
+            continue;
+        }
+        out.push(match c {
+            c if !is_unsafe(c) => c,
+            '\x7f' => '\u{2421}',
+            c if c < ' ' => char::from_u32(0x2400 + c as u32).unwrap_or('\u{fffd}'),
+            _ => '\u{fffd}',
+        });
+        rest = &rest[c.len_utf8()..];
+    }
+    Cow::Owned(out)
+}
+
+/// Returns `true` for control characters other than newline and tab.
+fn is_unsafe(c: char) -> bool {
+    c.is_control() && !matches!(c, '\n' | '\t')
+}
+
+/// Returns `true` if `url` may be emitted as a hyperlink: it uses one of the
+/// allowed schemes and contains no control characters that could end the
+/// OSC 8 sequence early.
+pub(crate) fn is_safe_url(url: &str) -> bool {
+    let Some((scheme, _)) = url.split_once(':') else {
+        return false;
+    };
+    URL_SCHEMES.iter().any(|allowed| scheme.eq_ignore_ascii_case(allowed)) && !url.chars().any(char::is_control)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_controls() {
+        assert!(matches!(sanitize("safe <text>\n"), Cow::Borrowed(_)));
+        assert_eq!(sanitize("\x00\x07\x7f"), "\u{2400}\u{2407}\u{2421}");
+        assert_eq!(sanitize("a\u{9b}31mb\u{85}"), "a\u{fffd}31mb\u{fffd}");
+        assert_eq!(sanitize("x\x1b"), "x");
+        assert_eq!(sanitize("\x1b]8;;https://evil\x1b\\click\x1b]8;;\x1b\\"), "click");
+        assert_eq!(sanitize("日本\x1b(B語"), "日本語");
+    }
+
+    #[test]
+    fn test_safe_urls() {
+        assert!(is_safe_url("https://x.dev/a?b=c"));
+        assert!(is_safe_url("MAILTO:me@x.dev"));
+        assert!(is_safe_url("file:///tmp/log.txt"));
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url("https://x.dev/\x1b]0;title\x07"));
+        assert!(!is_safe_url("https://x.dev/\u{9c}"));
+        assert!(!is_safe_url("x.dev"));
+    }
+}
@@ -6,6 +6,8 @@ use crate::style::Style;
 pub struct Span<'a> {
     pub text: Cow<'a, str>,
     pub style: Style<'a>,
+    /// Whether this is a horizontal rule, whose text is empty.
+    rule: bool,
 }
 
 impl<'a> Span<'a> {
@@ -13,15 +15,46 @@ impl<'a> Span<'a> {
         Self {
             text: text.into(),
             style,
+            rule: false,
         }
     }
 
+    /// Creates a horizontal rule, drawn across the width of the output when rendered.
+    pub fn rule(style: Style<'a>) -> Self {
+        Self {
+            text: Cow::Borrowed(""),
+            style,
+            rule: true,
+        }
+    }
+
+    /// Returns `true` if this span is a horizontal rule, made by [`Span::rule`]
+    /// or parsed from `<hr/>`.
+    pub fn is_rule(&self) -> bool {
+        self.rule
+    }
+
     pub fn into_owned(self) -> Span<'static> {
         Span {
             text: Cow::Owned(self.text.into_owned()),
             style: self.style.into_owned(),
+            rule: self.rule,
         }
     }
+
+    /// Returns the number of terminal columns the text occupies; for text
+    /// spanning several lines, the width of the widest line.
+    ///
+    /// Wide characters such as CJK ideographs and most emoji count as 2,
+    /// combining marks and escape sequences as 0. A rule counts as 80
+    /// columns, the width `Display` draws it with, even where a renderer draws
+    /// it at another width.
+    pub fn width(&self) -> usize {
+        if self.is_rule() {
+            return crate::render::DEFAULT_WIDTH;
+        }
+        crate::width::str_width(&self.text)
+    }
 }
 
 impl<'a> fmt::Display for Span<'a> {
@@ -76,7 +109,11 @@ impl<'a> fmt::Display for Span<'a> {
         }
 
         // Text
-        write!(f, "{}", self.text)?;
+        if self.is_rule() {
+            write!(f, "{}", crate::render::rule(crate::render::DEFAULT_WIDTH))?;
+        } else {
+            write!(f, "{}", self.text)?;
+        }
 
         // Hyperlink end
         if style.url.is_some() {
@@ -0,0 +1,275 @@
+//! Parsing markup incrementally, as it arrives in chunks.
+
+use std::ops::Range;
+use crate::error::ParseError;
+use crate::parser::{apply_token, check_closed, Frame, ParseOptions, Tokens};
+use crate::span::Span;
+
+/// How long a tag may grow, in bytes, before its `<` is taken as text.
+const DEFAULT_MAX_TAG_LEN: usize = 4096;
+
+/// An incremental markup parser for input that arrives in pieces, such as a
+/// log file being tailed or the output of a child process.
+///
+/// Feed it chunks of bytes with [`Parser::feed`] and it returns the spans that
+/// are complete so far. Tags and UTF-8 sequences split across chunks are held
+/// back until the rest arrives, and the style stack carries over, so the spans
+/// display the same as those of [`RichString::parse`](crate::RichString::parse)
+/// on the whole input, although text may be split into spans differently.
+/// Invalid UTF-8 is replaced with `�`.
+///
+/// Only an unfinished tag is ever buffered. A `<` that is not followed by a `>`
+/// within [`Parser::max_tag_len`] bytes is taken as text, as it would be at
+/// the end of the input.
+///
+/// ```rust
+/// use richparse::{Color, Parser};
+///
+/// let mut parser = Parser::new();
+/// let mut spans = parser.feed(b"<red>err").unwrap();
+/// spans.extend(parser.feed(b"or</r").unwrap());
+/// spans.extend(parser.feed(b"ed> d\xC3").unwrap());
+/// spans.extend(parser.feed(b"\xA9j\xC3\xA0 vu").unwrap());
+/// spans.extend(parser.finish().unwrap());
+///
+/// let text: String = spans.iter().map(|span| span.text.as_ref()).collect();
+/// assert_eq!(text, "error déjà vu");
+/// assert!(spans.iter().take(2).all(|span| span.style.fg == Color::Red));
+/// ```
+#[derive(Debug)]
+pub struct Parser<'r> {
+    options: ParseOptions<'r>,
+    max_tag_len: usize,
+    stack: Vec<Frame<'static>>,
+    /// The start of a UTF-8 sequence cut off by the end of the last chunk.
+    bytes: Vec<u8>,
+    /// Decoded text that has not been parsed yet: the start of an unfinished tag.
+    text: String,
+    /// The replacement characters in `text`, as their position in `text` and
+    /// the number of invalid bytes each stands for.
+    replacements: Vec<(usize, usize)>,
+    /// The offset of `text` in the bytes fed so far, for error spans.
+    offset: usize,
+}
+
+impl Parser<'static> {
+    /// Creates a parser with the default, lenient options.
+    pub fn new() -> Self {
+        Self::with_options(ParseOptions::new())
+    }
+}
+
+impl Default for Parser<'static> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'r> Parser<'r> {
+    /// Creates a parser with `options`; see [`RichString::parse_with_options`](crate::RichString::parse_with_options).
+    pub fn with_options(options: ParseOptions<'r>) -> Self {
+        Self {
+            options,
+            max_tag_len: DEFAULT_MAX_TAG_LEN,
+            stack: vec![Frame::base()],
+            bytes: Vec::new(),
+            text: String::new(),
+            replacements: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Sets how many bytes an unfinished tag may grow to, 4096 by default.
+    pub fn max_tag_len(mut self, len: usize) -> Self {
+        self.max_tag_len = len;
+        self
+    }
+
+    /// Parses the next chunk of input, returning the spans it completes.
+    ///
+    /// In strict mode an error is returned as soon as it is found, with its
+    /// span in bytes relative to the start of the whole input, as fed. The
+    /// parser should not be used after an error.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<Span<'static>>, ParseError> {
+        self.bytes.extend_from_slice(chunk);
+        self.decode(false);
+        self.parse(false)
+    }
+
+    /// Parses whatever input is left at the end, returning the remaining spans.
+    ///
+    /// In strict mode, tags that are still open are reported here.
+    pub fn finish(mut self) -> Result<Vec<Span<'static>>, ParseError> {
+        self.decode(true);
+        let spans = self.parse(true)?;
+        check_closed(&self.stack, &self.options)?;
+        Ok(spans)
+    }
+
+    /// Moves the complete UTF-8 text at the start of `bytes` to `text`,
+    /// keeping a sequence cut off at the end unless this is the `end` of the input.
+    fn decode(&mut self, end: bool) {
+        let mut start = 0;
+        while start < self.bytes.len() {
+            let (valid, invalid) = match std::str::from_utf8(&self.bytes[start..]) {
+                Ok(text) => (text, None),
+                Err(err) => {
+                    let (valid, _) = self.bytes[start..].split_at(err.valid_up_to());
+                    (std::str::from_utf8(valid).unwrap_or_default(), Some(err.error_len()))
+                }
+            };
+            self.text.push_str(valid);
+            start += valid.len();
+            match invalid {
+                None => break,
+                Some(Some(len)) => {
+                    self.replacements.push((self.text.len(), len));
+                    self.text.push('\u{fffd}');
+                    start += len;
+                }
+                // An incomplete sequence at the end of the input so far
+                Some(None) if end => {
+                    self.replacements.push((self.text.len(), self.bytes.len() - start));
+                    self.text.push('\u{fffd}');
+                    start = self.bytes.len();
+                }
+                Some(None) => break,
+            }
+        }
+        self.bytes.drain(..start);
+    }
+
+    /// Parses `text` as far as it is complete, or entirely at the `end` of the input.
+    fn parse(&mut self, end: bool) -> Result<Vec<Span<'static>>, ParseError> {
+        let mut text = std::mem::take(&mut self.text);
+        let mut stack: Vec<Frame<'_>> = std::mem::take(&mut self.stack);
+        let mut spans = Vec::new();
+        let mut cursor = 0;
+        let mut result = Ok(());
+
+        'parse: loop {
+            let rest = &text[cursor..];
+            let mut tokens = if end { Tokens::new(rest) } else { Tokens::partial(rest) };
+            for (token, span) in tokens.by_ref() {
+                let span = self.input_span(cursor + span.start..cursor + span.end);
+                if let Err(err) = apply_token(token, span, &mut stack, &mut spans, &self.options) {
+                    result = Err(err);
+                    break 'parse;
+                }
+            }
+            cursor += tokens.cursor();
+
+            // A '<' that has waited too long for its '>' is taken as it would be at the end
+            if text.len() - cursor > self.max_tag_len {
+                let mut tokens = Tokens::new(&text[cursor..]);
+                if let Some((token, span)) = tokens.next() {
+                    let span = self.input_span(cursor + span.start..cursor + span.end);
+                    if let Err(err) = apply_token(token, span, &mut stack, &mut spans, &self.options) {
+                        result = Err(err);
+                        break 'parse;
+                    }
+                    cursor += tokens.cursor();
+                    continue;
+                }
+            }
+            break;
+        }
+
+        let spans = spans.into_iter().map(Span::into_owned).collect();
+        self.stack = stack.into_iter().map(Frame::into_owned).collect();
+        text.drain(..cursor);
+        self.text = text;
+        self.offset += self.input_len(cursor);
+        self.replacements.retain(|(at, _)| *at >= cursor);
+        self.replacements.iter_mut().for_each(|(at, _)| *at -= cursor);
+        result.map(|()| spans)
+    }
+
+    /// Returns the span of the input, in bytes as fed, that `span` of `text` was decoded from.
+    fn input_span(&self, span: Range<usize>) -> Range<usize> {
+        self.offset + self.input_len(span.start)..self.offset + self.input_len(span.end)
+    }
+
+    /// Returns the number of input bytes that the first `len` bytes of `text` were decoded from.
+    fn input_len(&self, len: usize) -> usize {
+        self.replacements
+            .iter()
+            .take_while(|(at, _)| *at < len)
+            .fold(len, |len, (_, invalid)| len - '\u{fffd}'.len_utf8() + invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::RichString;
+    use crate::style::{Color, Style};
+
+    /// Feeds `input` to a parser in chunks of `size` bytes and normalizes the result.
+    fn parse_chunked(input: &str, size: usize) -> RichString<'static> {
+        let mut parser = Parser::new();
+        let mut spans = Vec::new();
+        for chunk in input.as_bytes().chunks(size) {
+            spans.extend(parser.feed(chunk).unwrap());
+        }
+        spans.extend(parser.finish().unwrap());
+        RichString::new(spans).normalize()
+    }
+
+    #[test]
+    fn test_stream_matches_parse() {
+        let input = "<red>a <b>bold</b></red> 日本 <<x> <link=\"https://x.dev/?a>b\">link</link> < y <hr/> end<";
+        let expected = RichString::parse(input).normalize();
+        for size in 1..=input.len() {
+            assert_eq!(parse_chunked(input, size), expected, "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_stream_yields_complete_spans() {
+        let mut parser = Parser::new();
+        assert_eq!(parser.feed(b"<b>one <re").unwrap(), [Span::new("one ", Style::new().bold())]);
+        assert_eq!(parser.feed(b"d>two").unwrap(), [Span::new("two", Style::new().bold().fg(Color::Red))]);
+        assert!(parser.feed(b"<").unwrap().is_empty());
+        assert_eq!(parser.finish().unwrap(), [Span::new("<", Style::new().bold().fg(Color::Red))]);
+    }
+
+    #[test]
+    fn test_stream_invalid_utf8() {
+        let mut parser = Parser::new();
+        let mut spans = parser.feed(b"a\xffb\xe6\x97").unwrap();
+        spans.extend(parser.finish().unwrap());
+        assert_eq!(RichString::new(spans).plain().to_string(), "a\u{fffd}b\u{fffd}");
+    }
+
+    #[test]
+    fn test_stream_max_tag_len() {
+        let mut parser = Parser::new().max_tag_len(4);
+        assert_eq!(parser.feed(b"a <b").unwrap().len(), 1);
+        let spans = parser.feed(b"cdef").unwrap();
+        assert_eq!(RichString::new(spans).plain().to_string(), "<bcdef");
+    }
+
+    #[test]
+    fn test_stream_strict_errors() {
+        let options = ParseOptions::new().strict();
+        let mut parser = Parser::with_options(options);
+        parser.feed(b"ok <b>x</b>").unwrap();
+        let err = parser.feed(b" <bo").and_then(|_| parser.feed(b"dl>")).unwrap_err();
+        assert_eq!(err, ParseError::UnknownTag { tag: "bodl".to_string(), span: 12..18 });
+
+        // Spans count the bytes fed, not the replacement characters they decode to
+        let mut parser = Parser::with_options(options);
+        parser.feed(b"\xff\xe6\x97 <bo").unwrap();
+        let err = parser.feed(b"dl>").unwrap_err();
+        assert_eq!(err, ParseError::UnknownTag { tag: "bodl".to_string(), span: 4..10 });
+
+        let mut parser = Parser::with_options(options);
+        parser.feed(b"\xff<b>x").unwrap();
+        assert!(matches!(parser.finish(), Err(ParseError::UnclosedTag { span, .. }) if span == (1..4)));
+
+        let mut parser = Parser::with_options(options);
+        parser.feed(b"<b>x").unwrap();
+        assert!(matches!(parser.finish(), Err(ParseError::UnclosedTag { span, .. }) if span == (0..3)));
+    }
+}
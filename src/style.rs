@@ -1,4 +1,8 @@
 use std::borrow::Cow;
+use std::str::FromStr;
+use crate::error::ParseError;
+use crate::palette;
+use crate::render::ColorDepth;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
@@ -24,81 +28,166 @@ pub enum Color {
 }
 
 impl Color {
+    /// Returns the closest color that can be displayed at `depth`.
+    ///
+    /// RGB colors map to the nearest xterm-256 entry, and 256-color or RGB
+    /// values map to the nearest of the 16 basic colors by perceptual distance.
+    /// With [`ColorDepth::None`] every color becomes `Color::Default`.
+    ///
+    /// ```rust
+    /// use richparse::{Color, ColorDepth};
+    ///
+    /// assert_eq!(Color::Rgb(255, 135, 0).downgrade(ColorDepth::Ansi256), Color::Ansi256(208));
+    /// assert_eq!(Color::Rgb(250, 10, 10).downgrade(ColorDepth::Ansi16), Color::BrightRed);
+    /// assert_eq!(Color::Red.downgrade(ColorDepth::None), Color::Default);
+    /// ```
+    pub fn downgrade(self, depth: ColorDepth) -> Color {
+        match (depth, self) {
+            (ColorDepth::None, _) => Color::Default,
+            (ColorDepth::TrueColor, color) => color,
+            (ColorDepth::Ansi256, Color::Rgb(r, g, b)) => Color::Ansi256(palette::rgb_to_ansi256((r, g, b))),
+            (ColorDepth::Ansi16, Color::Ansi256(n)) if n < 16 => Color::from_ansi16(n),
+            (ColorDepth::Ansi16, Color::Ansi256(n)) => Color::from_ansi16(palette::nearest_ansi16(palette::ansi256_to_rgb(n))),
+            (ColorDepth::Ansi16, Color::Rgb(r, g, b)) => Color::from_ansi16(palette::nearest_ansi16((r, g, b))),
+            (_, color) => color,
+        }
+    }
+
+    /// Returns the basic color with the given index (0-15); larger values wrap around.
+    pub(crate) fn from_ansi16(index: u8) -> Color {
+        match index % 16 {
+            0 => Color::Black,
+            1 => Color::Red,
+            2 => Color::Green,
+            3 => Color::Yellow,
+            4 => Color::Blue,
+            5 => Color::Magenta,
+            6 => Color::Cyan,
+            7 => Color::White,
+            8 => Color::BrightBlack,
+            9 => Color::BrightRed,
+            10 => Color::BrightGreen,
+            11 => Color::BrightYellow,
+            12 => Color::BrightBlue,
+            13 => Color::BrightMagenta,
+            14 => Color::BrightCyan,
+            _ => Color::BrightWhite,
+        }
+    }
+
+    /// Returns the index (0-15) of a basic color, or `None` for other colors.
+    pub(crate) fn ansi16_index(&self) -> Option<u8> {
+        let index = match self {
+            Color::Black => 0,
+            Color::Red => 1,
+            Color::Green => 2,
+            Color::Yellow => 3,
+            Color::Blue => 4,
+            Color::Magenta => 5,
+            Color::Cyan => 6,
+            Color::White => 7,
+            Color::BrightBlack => 8,
+            Color::BrightRed => 9,
+            Color::BrightGreen => 10,
+            Color::BrightYellow => 11,
+            Color::BrightBlue => 12,
+            Color::BrightMagenta => 13,
+            Color::BrightCyan => 14,
+            Color::BrightWhite => 15,
+            _ => return None,
+        };
+        Some(index)
+    }
+
     pub fn as_ansi_fg(&self) -> String {
+        format!("\x1b[{}m", self.sgr_fg())
+    }
+
+    pub fn as_ansi_bg(&self) -> String {
+        format!("\x1b[{}m", self.sgr_bg())
+    }
+
+    pub fn as_ansi_underline_color(&self) -> String {
+        format!("\x1b[{}m", self.sgr_underline_color())
+    }
+
+    /// The SGR parameters selecting this color as the foreground, e.g. `31` or `38;5;208`.
+    pub(crate) fn sgr_fg(&self) -> String {
         match self {
-            Color::Black => "\x1b[30m".to_string(),
-            Color::Red => "\x1b[31m".to_string(),
-            Color::Green => "\x1b[32m".to_string(),
-            Color::Yellow => "\x1b[33m".to_string(),
-            Color::Blue => "\x1b[34m".to_string(),
-            Color::Magenta => "\x1b[35m".to_string(),
-            Color::Cyan => "\x1b[36m".to_string(),
-            Color::White => "\x1b[37m".to_string(),
-            Color::BrightBlack => "\x1b[90m".to_string(),
-            Color::BrightRed => "\x1b[91m".to_string(),
-            Color::BrightGreen => "\x1b[92m".to_string(),
-            Color::BrightYellow => "\x1b[93m".to_string(),
-            Color::BrightBlue => "\x1b[94m".to_string(),
-            Color::BrightMagenta => "\x1b[95m".to_string(),
-            Color::BrightCyan => "\x1b[96m".to_string(),
-            Color::BrightWhite => "\x1b[97m".to_string(),
-            Color::Default => "\x1b[39m".to_string(),
-            Color::Ansi256(n) => format!("\x1b[38;5;{}m", n),
-            Color::Rgb(r, g, b) => format!("\x1b[38;2;{};{};{}m", r, g, b),
+            Color::Black => "30".to_string(),
+            Color::Red => "31".to_string(),
+            Color::Green => "32".to_string(),
+            Color::Yellow => "33".to_string(),
+            Color::Blue => "34".to_string(),
+            Color::Magenta => "35".to_string(),
+            Color::Cyan => "36".to_string(),
+            Color::White => "37".to_string(),
+            Color::BrightBlack => "90".to_string(),
+            Color::BrightRed => "91".to_string(),
+            Color::BrightGreen => "92".to_string(),
+            Color::BrightYellow => "93".to_string(),
+            Color::BrightBlue => "94".to_string(),
+            Color::BrightMagenta => "95".to_string(),
+            Color::BrightCyan => "96".to_string(),
+            Color::BrightWhite => "97".to_string(),
+            Color::Default => "39".to_string(),
+            Color::Ansi256(n) => format!("38;5;{}", n),
+            Color::Rgb(r, g, b) => format!("38;2;{};{};{}", r, g, b),
         }
     }
 
-    pub fn as_ansi_bg(&self) -> String {
+    /// The SGR parameters selecting this color as the background, e.g. `41` or `48;5;208`.
+    pub(crate) fn sgr_bg(&self) -> String {
         match self {
-            // Standard colors mapped to 256-color palette
-            Color::Black => "\x1b[40m".to_string(),
-            Color::Red => "\x1b[41m".to_string(),
-            Color::Green => "\x1b[42m".to_string(),
-            Color::Yellow => "\x1b[43m".to_string(),
-            Color::Blue => "\x1b[44m".to_string(),
-            Color::Magenta => "\x1b[45m".to_string(),
-            Color::Cyan => "\x1b[46m".to_string(),
-            Color::White => "\x1b[47m".to_string(),
-            Color::BrightBlack => "\x1b[100m".to_string(),
-            Color::BrightRed => "\x1b[101m".to_string(),
-            Color::BrightGreen => "\x1b[102m".to_string(),
-            Color::BrightYellow => "\x1b[103m".to_string(),
-            Color::BrightBlue => "\x1b[104m".to_string(),
-            Color::BrightMagenta => "\x1b[105m".to_string(),
-            Color::BrightCyan => "\x1b[106m".to_string(),
-            Color::BrightWhite => "\x1b[107m".to_string(),
-            Color::Default => "\x1b[49m".to_string(), // Reset
+            Color::Black => "40".to_string(),
+            Color::Red => "41".to_string(),
+            Color::Green => "42".to_string(),
+            Color::Yellow => "43".to_string(),
+            Color::Blue => "44".to_string(),
+            Color::Magenta => "45".to_string(),
+            Color::Cyan => "46".to_string(),
+            Color::White => "47".to_string(),
+            Color::BrightBlack => "100".to_string(),
+            Color::BrightRed => "101".to_string(),
+            Color::BrightGreen => "102".to_string(),
+            Color::BrightYellow => "103".to_string(),
+            Color::BrightBlue => "104".to_string(),
+            Color::BrightMagenta => "105".to_string(),
+            Color::BrightCyan => "106".to_string(),
+            Color::BrightWhite => "107".to_string(),
+            Color::Default => "49".to_string(), // Reset
             
             //Additonal color mapping
-            Color::Ansi256(n) => format!("\x1b[48;5;{}m", n),
-            Color::Rgb(r, g, b) => format!("\x1b[48;2;{};{};{}m", r, g, b),
+            Color::Ansi256(n) => format!("48;5;{}", n),
+            Color::Rgb(r, g, b) => format!("48;2;{};{};{}", r, g, b),
         }
     }
 
-    pub fn as_ansi_underline_color(&self) -> String {
+    /// The SGR parameters selecting this color for underlines, e.g. `58;5;1`.
+    pub(crate) fn sgr_underline_color(&self) -> String {
         match self {
             // Standard colors mapped to 256-color palette
-            Color::Black => "\x1b[58;5;0m".to_string(),
-            Color::Red => "\x1b[58;5;1m".to_string(),
-            Color::Green => "\x1b[58;5;2m".to_string(),
-            Color::Yellow => "\x1b[58;5;3m".to_string(),
-            Color::Blue => "\x1b[58;5;4m".to_string(),
-            Color::Magenta => "\x1b[58;5;5m".to_string(),
-            Color::Cyan => "\x1b[58;5;6m".to_string(),
-            Color::White => "\x1b[58;5;7m".to_string(),
-            Color::BrightBlack => "\x1b[58;5;8m".to_string(),
-            Color::BrightRed => "\x1b[58;5;9m".to_string(),
-            Color::BrightGreen => "\x1b[58;5;10m".to_string(),
-            Color::BrightYellow => "\x1b[58;5;11m".to_string(),
-            Color::BrightBlue => "\x1b[58;5;12m".to_string(),
-            Color::BrightMagenta => "\x1b[58;5;13m".to_string(),
-            Color::BrightCyan => "\x1b[58;5;14m".to_string(),
-            Color::BrightWhite => "\x1b[58;5;15m".to_string(),
-            Color::Default => "\x1b[59m".to_string(), // Reset
+            Color::Black => "58;5;0".to_string(),
+            Color::Red => "58;5;1".to_string(),
+            Color::Green => "58;5;2".to_string(),
+            Color::Yellow => "58;5;3".to_string(),
+            Color::Blue => "58;5;4".to_string(),
+            Color::Magenta => "58;5;5".to_string(),
+            Color::Cyan => "58;5;6".to_string(),
+            Color::White => "58;5;7".to_string(),
+            Color::BrightBlack => "58;5;8".to_string(),
+            Color::BrightRed => "58;5;9".to_string(),
+            Color::BrightGreen => "58;5;10".to_string(),
+            Color::BrightYellow => "58;5;11".to_string(),
+            Color::BrightBlue => "58;5;12".to_string(),
+            Color::BrightMagenta => "58;5;13".to_string(),
+            Color::BrightCyan => "58;5;14".to_string(),
+            Color::BrightWhite => "58;5;15".to_string(),
+            Color::Default => "59".to_string(), // Reset
             
             // Additional color mapping
-            Color::Ansi256(n) => format!("\x1b[58;5;{}m", n),
-            Color::Rgb(r, g, b) => format!("\x1b[58;2;{};{};{}m", r, g, b),
+            Color::Ansi256(n) => format!("58;5;{}", n),
+            Color::Rgb(r, g, b) => format!("58;2;{};{};{}", r, g, b),
         }
     }
 }
@@ -149,6 +238,24 @@ impl<'a> Style<'a> {
         Self::default()
     }
 
+    /// Parses a style expression such as `bold italic #ff8800 on bright_black u=red`,
+    /// borrowing any link target from `expr`.
+    ///
+    /// The expression is a space-separated list of modifiers (`bold`, `u`, ...),
+    /// foreground colors, `on <color>` for the background, and `key=value`
+    /// attributes accepted by tags (`fg=`, `bg=`, `u=`, `uu=`, `cu=`, `link=`).
+    ///
+    /// ```rust
+    /// use richparse::{Color, Style};
+    ///
+    /// let style = Style::parse("bold #ff8800 on bright_black u=red").unwrap();
+    /// assert_eq!(style, Style::new().bold().fg(Color::Rgb(255, 136, 0)).bg(Color::BrightBlack)
+    ///     .underline().underline_color(Color::Red));
+    /// ```
+    pub fn parse(expr: &'a str) -> Result<Self, ParseError> {
+        crate::parser::parse_style(expr)
+    }
+
     pub fn fg(mut self, color: Color) -> Self {
         self.fg = color;
         self
@@ -224,6 +331,37 @@ impl<'a> Style<'a> {
         self
     }
 
+    /// Applies every attribute set in `other` on top of this style.
+    ///
+    /// Colors equal to `Color::Default` and modifiers that are `false` in
+    /// `other` leave this style unchanged.
+    pub fn merge(mut self, other: &Style<'a>) -> Self {
+        if other.fg != Color::Default {
+            self.fg = other.fg;
+        }
+        if other.bg != Color::Default {
+            self.bg = other.bg;
+        }
+        self.bold |= other.bold;
+        self.italic |= other.italic;
+        self.underline |= other.underline;
+        self.double_underline |= other.double_underline;
+        self.curly_underline |= other.curly_underline;
+        self.overline |= other.overline;
+        self.strikethrough |= other.strikethrough;
+        self.dim |= other.dim;
+        self.blink |= other.blink;
+        self.inverse |= other.inverse;
+        self.hidden |= other.hidden;
+        if other.url.is_some() {
+            self.url = other.url.clone();
+        }
+        if other.underline_color.is_some() {
+            self.underline_color = other.underline_color;
+        }
+        self
+    }
+
     pub fn into_owned(self) -> Style<'static> {
         Style {
             fg: self.fg,
@@ -243,4 +381,13 @@ impl<'a> Style<'a> {
             underline_color: self.underline_color,
         }
     }
-}
\ No newline at end of file
+}
+
+impl<'a> FromStr for Style<'a> {
+    type Err = ParseError;
+
+    /// Parses a style expression; see [`Style::parse`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Style::parse(s).map(Style::into_owned)
+    }
+}
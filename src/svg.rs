@@ -0,0 +1,390 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use crate::palette::Palette;
+use crate::parser::RichString;
+use crate::render::{rule, DEFAULT_WIDTH};
+use crate::sanitize::{is_safe_url, sanitize};
+use crate::style::{Color, Style};
+use crate::width::str_width;
+
+/// Options controlling how a [`RichString`] is exported to SVG.
+///
+/// Sizes are in pixels. Every terminal column occupies one cell of
+/// `font_size * char_width` by `font_size * line_height`, so wide characters
+/// such as CJK ideographs take two cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    pub palette: Palette,
+    pub font_family: String,
+    pub font_size: f32,
+    /// The line height as a multiple of the font size.
+    pub line_height: f32,
+    /// The width of one character as a multiple of the font size.
+    pub char_width: f32,
+    /// The space between the window border and the text.
+    pub padding: f32,
+    /// Whether to draw a title bar with window buttons above the text.
+    pub window: bool,
+    /// Text shown centered in the title bar.
+    pub title: Option<String>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            palette: Palette::default(),
+            font_family: "ui-monospace, SFMono-Regular, Menlo, Consolas, monospace".to_string(),
+            font_size: 14.0,
+            line_height: 1.4,
+            char_width: 0.6,
+            padding: 16.0,
+            window: true,
+            title: None,
+        }
+    }
+}
+
+impl SvgOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    pub fn font_family<S: Into<String>>(mut self, family: S) -> Self {
+        self.font_family = family.into();
+        self
+    }
+
+    pub fn font_size(mut self, size: f32) -> Self {
+        self.font_size = size;
+        self
+    }
+
+    pub fn window(mut self, window: bool) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+}
+
+type Rgb = (u8, u8, u8);
+
+/// The height of the title bar drawn when [`SvgOptions::window`] is set.
+const TITLE_BAR: f32 = 32.0;
+
+/// Renders `lines` as a standalone SVG image of a terminal window, one row per
+/// line; newlines inside a line start further rows.
+///
+/// ```rust
+/// use richparse::RichString;
+/// use richparse::svg::{self, SvgOptions};
+///
+/// let lines = [RichString::parse("<green>$</green> cargo build"), RichString::parse("<bold>Finished</bold>")];
+/// let image = svg::render_lines(&lines, &SvgOptions::new().title("build"));
+/// assert!(image.starts_with("<svg"));
+/// ```
+pub fn render_lines(lines: &[RichString<'_>], options: &SvgOptions) -> String {
+    // Split spans into rows of (text, style) segments, joining adjacent segments
+    // with the same style so each run becomes a single <text>
+    let mut rows: Vec<Vec<(Cow<'_, str>, &Style<'_>)>> = Vec::new();
+    // Rules are filled in once the width of the image is known
+    let mut rules = Vec::new();
+    for line in lines {
+        rows.push(Vec::new());
+        for span in &line.spans {
+            let last = rows.len() - 1;
+            if span.is_rule() {
+                rules.push((last, rows[last].len()));
+                rows[last].push((Cow::Borrowed(""), &span.style));
+                continue;
+            }
+            for line in span.text.split_inclusive('\n') {
+                let (part, newline) = match line.strip_suffix('\n') {
+                    Some(part) => (part.strip_suffix('\r').unwrap_or(part), true),
+                    None => (line, false),
+                };
+                // Control characters are not allowed in XML, so show them as sanitize does
+                let part = sanitize(part);
+                let Some(row) = rows.last_mut() else { continue };
+                match row.last_mut() {
+                    _ if part.is_empty() => {}
+                    // Only rules are empty here, and they are never extended
+                    Some((text, style)) if **style == span.style && !text.is_empty() => text.to_mut().push_str(&part),
+                    _ => row.push((part, &span.style)),
+                }
+                if newline {
+                    rows.push(Vec::new());
+                }
+            }
+        }
+    }
+
+    let palette = &options.palette;
+    let cell_width = options.font_size * options.char_width;
+    let row_height = options.font_size * options.line_height;
+    let columns = rows
+        .iter()
+        .map(|row| row.iter().map(|(text, _)| str_width(text)).sum::<usize>())
+        .max()
+        .unwrap_or(0);
+    let columns = if columns == 0 && !rules.is_empty() { DEFAULT_WIDTH } else { columns };
+    for (row, index) in rules {
+        let used: usize = rows[row].iter().map(|(text, _)| str_width(text)).sum();
+        rows[row][index].0 = Cow::Owned(rule(columns.saturating_sub(used)));
+    }
+    let top = if options.window { TITLE_BAR } else { 0.0 };
+    let width = columns as f32 * cell_width + 2.0 * options.padding;
+    let height = top + rows.len() as f32 * row_height + 2.0 * options.padding;
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+        w = num(width),
+        h = num(height),
+    );
+    let _ = write!(
+        out,
+        "<style>text {{ font-family: {}; font-size: {}px; white-space: pre; }}</style>",
+        escape(&options.font_family),
+        num(options.font_size),
+    );
+    let _ = write!(
+        out,
+        "<rect width=\"100%\" height=\"100%\" rx=\"8\" fill=\"{}\"/>",
+        hex(palette.background)
+    );
+
+    if options.window {
+        for (i, color) in ["#ff5f57", "#febc2e", "#28c840"].iter().enumerate() {
+            let _ = write!(
+                out,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"6\" fill=\"{}\"/>",
+                num(20.0 + i as f32 * 20.0),
+                num(TITLE_BAR / 2.0),
+                color
+            );
+        }
+        if let Some(title) = &options.title {
+            let _ = write!(
+                out,
+                "<text x=\"50%\" y=\"{}\" text-anchor=\"middle\" fill=\"{}\" opacity=\"0.6\">{}</text>",
+                num(TITLE_BAR / 2.0 + options.font_size * 0.35),
+                hex(palette.foreground),
+                escape(title)
+            );
+        }
+    }
+
+    let _ = write!(out, "<g transform=\"translate({}, {})\">", num(options.padding), num(top + options.padding));
+    for (row_index, row) in rows.iter().enumerate() {
+        let y = row_index as f32 * row_height;
+        let baseline = y + row_height * 0.75;
+        let mut column = 0;
+        for (text, style) in row {
+            let x = column as f32 * cell_width;
+            let len = str_width(text);
+            column += len;
+
+            let (fg, bg) = colors(style, palette);
+            if let Some(bg) = bg {
+                let _ = write!(
+                    out,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    num(x),
+                    num(y),
+                    num(len as f32 * cell_width),
+                    num(row_height),
+                    hex(bg)
+                );
+            }
+            if style.hidden || text.trim().is_empty() {
+                continue;
+            }
+
+            // Links with other schemes, such as `javascript:`, are drawn as plain text
+            let url = style.url.as_deref().filter(|url| is_safe_url(url));
+            if let Some(url) = url {
+                let _ = write!(out, "<a href=\"{}\">", escape(url));
+            }
+            let _ = write!(out, "<text x=\"{}\" y=\"{}\" fill=\"{}\"", num(x), num(baseline), hex(fg));
+            if len != text.chars().count() {
+                // Fit runs with wide or zero-width characters to their cells, as the font may not
+                let _ = write!(out, " textLength=\"{}\" lengthAdjust=\"spacingAndGlyphs\"", num(len as f32 * cell_width));
+            }
+            if style.bold {
+                out.push_str(" font-weight=\"bold\"");
+            }
+            if style.italic {
+                out.push_str(" font-style=\"italic\"");
+            }
+            if style.dim {
+                out.push_str(" opacity=\"0.5\"");
+            }
+            if let Some(decoration) = decoration(style, palette) {
+                let _ = write!(out, " style=\"text-decoration: {}\"", decoration);
+            }
+            let _ = write!(out, ">{}</text>", escape(text));
+            if url.is_some() {
+                out.push_str("</a>");
+            }
+        }
+    }
+    out.push_str("</g></svg>");
+    out
+}
+
+/// Returns the text color and the background color (if it differs from the
+/// window's) of `style`, with inverse applied.
+fn colors(style: &Style<'_>, palette: &Palette) -> (Rgb, Option<Rgb>) {
+    let fg = palette.rgb(style.fg);
+    let bg = palette.rgb(style.bg);
+    if style.inverse {
+        (bg.unwrap_or(palette.background), Some(fg.unwrap_or(palette.foreground)))
+    } else {
+        (fg.unwrap_or(palette.foreground), bg)
+    }
+}
+
+/// Returns the CSS `text-decoration` value for `style`, if it has any lines.
+fn decoration(style: &Style<'_>, palette: &Palette) -> Option<String> {
+    let underline = style.underline || style.double_underline || style.curly_underline;
+    let mut parts: Vec<String> = [
+        (underline, "underline"),
+        (style.overline, "overline"),
+        (style.strikethrough, "line-through"),
+    ]
+    .into_iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, line)| line.to_string())
+    .collect();
+    if parts.is_empty() {
+        return None;
+    }
+    if style.curly_underline {
+        parts.push("wavy".to_string());
+    } else if style.double_underline {
+        parts.push("double".to_string());
+    }
+    if underline && let Some(rgb) = style.underline_color.and_then(|color: Color| palette.rgb(color)) {
+        parts.push(hex(rgb));
+    }
+    Some(parts.join(" "))
+}
+
+/// Formats a length with at most two decimals, dropping trailing zeros.
+fn num(value: f32) -> String {
+    let formatted = format!("{:.2}", value);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn hex((r, g, b): Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Escapes the characters that are special in XML text and attributes.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> SvgOptions {
+        // 10x20 cells with no padding or window make positions easy to check
+        SvgOptions {
+            font_size: 10.0,
+            line_height: 2.0,
+            char_width: 1.0,
+            padding: 0.0,
+            window: false,
+            ..SvgOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_svg_layout() {
+        let rs = RichString::parse("ab\n<style=\"red on blue\">c</style> <<d");
+        let svg = rs.to_svg(&options());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"40\" height=\"40\""));
+        assert!(svg.contains("<text x=\"0\" y=\"15\" fill=\"#e5e5e5\">ab</text>"));
+        assert!(svg.contains("<rect x=\"0\" y=\"20\" width=\"10\" height=\"20\" fill=\"#0000ee\"/>"));
+        assert!(svg.contains("<text x=\"0\" y=\"35\" fill=\"#cd0000\">c</text>"));
+        assert!(svg.contains("<text x=\"10\" y=\"35\" fill=\"#e5e5e5\"> &lt;d</text>"));
+        assert!(svg.ends_with("</g></svg>"));
+    }
+
+    #[test]
+    fn test_svg_wide_characters() {
+        let rs = RichString::parse("日本<b>x</b>\n<bg=blue>語</bg>");
+        let svg = rs.to_svg(&options());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"50\" height=\"40\""));
+        assert!(svg.contains(
+            "<text x=\"0\" y=\"15\" fill=\"#e5e5e5\" textLength=\"40\" lengthAdjust=\"spacingAndGlyphs\">日本</text>"
+        ));
+        assert!(svg.contains("<text x=\"40\" y=\"15\" fill=\"#e5e5e5\" font-weight=\"bold\">x</text>"));
+        assert!(svg.contains("<rect x=\"0\" y=\"20\" width=\"20\" height=\"20\" fill=\"#0000ee\"/>"));
+    }
+
+    #[test]
+    fn test_svg_styles() {
+        let rs = RichString::parse("<b><i><cu=red><link=https://x.dev>a</link></cu></i></b><inverse>b</inverse>");
+        let svg = rs.to_svg(&options());
+        assert!(svg.contains(
+            "<a href=\"https://x.dev\"><text x=\"0\" y=\"15\" fill=\"#e5e5e5\" font-weight=\"bold\" font-style=\"italic\" \
+             style=\"text-decoration: underline wavy #cd0000\">a</text></a>"
+        ));
+        assert!(svg.contains("<rect x=\"10\" y=\"0\" width=\"10\" height=\"20\" fill=\"#e5e5e5\"/>"));
+        assert!(svg.contains("<text x=\"10\" y=\"15\" fill=\"#000000\">b</text>"));
+    }
+
+    #[test]
+    fn test_svg_unsafe_links() {
+        let svg = RichString::parse("<link=\"javascript:alert(1)\">a</link>").to_svg(&options());
+        assert!(!svg.contains("<a "));
+        assert!(svg.contains("<text x=\"0\" y=\"15\" fill=\"#e5e5e5\">a</text>"));
+    }
+
+    #[test]
+    fn test_svg_control_characters() {
+        let svg = RichString::parse("a\x1b[2Jb\x07c\u{85}d\r\ne").to_svg(&options());
+        assert!(!svg.contains(|c: char| c.is_control() && c != '\n'));
+        assert!(svg.contains("<text x=\"0\" y=\"15\" fill=\"#e5e5e5\">ab\u{2407}c\u{fffd}d</text>"));
+        assert!(svg.contains("<text x=\"0\" y=\"35\" fill=\"#e5e5e5\">e</text>"));
+    }
+
+    #[test]
+    fn test_svg_window() {
+        let svg = RichString::parse("x").to_svg(&SvgOptions::new().title("a & b"));
+        assert!(svg.contains("<circle"));
+        assert!(svg.contains(">a &amp; b</text>"));
+        assert!(!RichString::parse("x").to_svg(&options()).contains("<circle"));
+    }
+
+    #[test]
+    fn test_svg_rule() {
+        let lines = [RichString::parse("abcd"), RichString::parse("a<hr/>")];
+        let image = render_lines(&lines, &SvgOptions::new().window(false));
+        assert!(image.contains(">───</text>"));
+    }
+}
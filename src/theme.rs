@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use crate::error::ThemeError;
+use crate::parser::parse_style;
+use crate::registry::TagRegistry;
+
+/// A mapping from tag names to style expressions such as `"bold red on #202020"`.
+///
+/// Themes are plain data: with the `theme` feature they can be loaded from
+/// TOML or JSON, so a palette can change without recompiling. A theme may
+/// name a parent in `inherits`; styles it does not define are taken from the
+/// parent (see [`Theme::resolve`]).
+///
+/// ```toml
+/// name = "dark"
+/// inherits = "base"
+///
+/// [styles]
+/// error = "bold red"
+/// path = "cyan underline"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "theme", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "theme", serde(default))]
+pub struct Theme {
+    pub name: Option<String>,
+    pub inherits: Option<String>,
+    pub styles: BTreeMap<String, String>,
+}
+
+impl Theme {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the style expression for `tag`, replacing any previous one.
+    pub fn insert<T: Into<String>, S: Into<String>>(&mut self, tag: T, style: S) -> &mut Self {
+        self.styles.insert(tag.into().to_lowercase(), style.into());
+        self
+    }
+
+    /// Returns a theme with the styles of `parent` that this theme does not override.
+    ///
+    /// The result inherits from whatever `parent` inherits from, so a chain can be
+    /// flattened by calling this once per ancestor.
+    pub fn inherit(mut self, parent: &Theme) -> Theme {
+        for (tag, style) in &parent.styles {
+            self.styles.entry(tag.clone()).or_insert_with(|| style.clone());
+        }
+        self.inherits = parent.inherits.clone();
+        self
+    }
+
+    /// Flattens the `inherits` chain of this theme, looking parents up by name in `themes`.
+    pub fn resolve(&self, themes: &[Theme]) -> Result<Theme, ThemeError> {
+        let mut resolved = self.clone();
+        let mut seen: Vec<&str> = self.name.as_deref().into_iter().collect();
+
+        while let Some(parent_name) = resolved.inherits.clone() {
+            if seen.contains(&parent_name.as_str()) {
+                return Err(ThemeError::InheritanceCycle(parent_name));
+            }
+            let parent = themes
+                .iter()
+                .find(|theme| theme.name.as_deref() == Some(parent_name.as_str()))
+                .ok_or_else(|| ThemeError::UnknownParent(parent_name.clone()))?;
+            seen.extend(parent.name.as_deref());
+            resolved = resolved.inherit(parent);
+        }
+
+        Ok(resolved)
+    }
+
+    /// Registers every style of this theme as a custom tag in `registry`.
+    pub fn register_into(&self, registry: &mut TagRegistry) -> Result<(), ThemeError> {
+        for (tag, expr) in &self.styles {
+            let style = parse_style(expr).map_err(|error| ThemeError::InvalidStyle {
+                tag: tag.clone(),
+                error,
+            })?;
+            registry.register(tag.as_str(), style.into_owned());
+        }
+        Ok(())
+    }
+
+    /// Builds a [`TagRegistry`] containing the styles of this theme, which take
+    /// precedence over the built-in tags of the same name.
+    ///
+    /// Call [`Theme::resolve`] first if the theme inherits from another one.
+    ///
+    /// ```rust
+    /// use richparse::{Color, RichString, Theme};
+    ///
+    /// let mut theme = Theme::new();
+    /// theme.insert("error", "bold red").insert("path", "cyan underline");
+    ///
+    /// let registry = theme.registry().unwrap();
+    /// let rs = RichString::parse_with(&registry, "<error>Failed</error>");
+    /// assert_eq!(rs.spans[0].style.fg, Color::Red);
+    /// ```
+    pub fn registry(&self) -> Result<TagRegistry, ThemeError> {
+        let mut registry = TagRegistry::new();
+        self.register_into(&mut registry)?;
+        Ok(registry)
+    }
+
+    /// Parses a theme from TOML.
+    #[cfg(feature = "theme")]
+    pub fn from_toml(source: &str) -> Result<Theme, ThemeError> {
+        toml::from_str(source).map_err(ThemeError::Toml)
+    }
+
+    /// Parses a theme from JSON.
+    #[cfg(feature = "theme")]
+    pub fn from_json(source: &str) -> Result<Theme, ThemeError> {
+        serde_json::from_str(source).map_err(ThemeError::Json)
+    }
+
+    /// Loads a theme from a `.toml` or `.json` file.
+    #[cfg(feature = "theme")]
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Theme, ThemeError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(ThemeError::Io)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::from_json(&source),
+            _ => Self::from_toml(&source),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use crate::parser::RichString;
+use crate::render::{rule, DEFAULT_WIDTH};
+use crate::span::Span;
+use crate::width::graphemes;
+
+/// Where [`RichString::truncate`] removes text and puts the `…`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ellipsis {
+    /// Keep the start: `long te…`
+    #[default]
+    End,
+    /// Keep both ends: `long…ext`
+    Middle,
+    /// Keep the end: `…ng text`
+    Start,
+}
+
+const ELLIPSIS: &str = "…";
+
+/// Shortens `rich` to at most `width` columns; see [`RichString::truncate`].
+pub(crate) fn truncate<'a>(rich: &RichString<'a>, width: usize, ellipsis: Ellipsis) -> RichString<'a> {
+    shorten(rich, width, Some(ellipsis))
+}
+
+/// Keeps the first `width` columns of `rich`, without an ellipsis.
+pub(crate) fn cut<'a>(rich: &RichString<'a>, width: usize) -> RichString<'a> {
+    shorten(rich, width, None)
+}
+
+fn shorten<'a>(rich: &RichString<'a>, width: usize, ellipsis: Option<Ellipsis>) -> RichString<'a> {
+    let graphemes = split_graphemes(rich);
+    let total: usize = graphemes.iter().map(|g| g.width).sum();
+    if total <= width {
+        return rich.clone();
+    }
+    // Escape sequences embedded in the text are dropped, since the cut could
+    // separate one from the sequence that resets it
+    let graphemes: Vec<_> = graphemes
+        .into_iter()
+        .filter(|g| !rich.spans[g.span].text[g.range.clone()].starts_with('\x1b'))
+        .collect();
+    if width == 0 {
+        return RichString::new(Vec::new());
+    }
+
+    // One column goes to the ellipsis
+    let budget = width - usize::from(ellipsis.is_some());
+    let (head_budget, tail_budget) = match ellipsis {
+        None | Some(Ellipsis::End) => (budget, 0),
+        Some(Ellipsis::Start) => (0, budget),
+        Some(Ellipsis::Middle) => (budget - budget / 2, budget / 2),
+    };
+    let fits = |budget: usize| {
+        let mut used = 0;
+        move |width: &usize| {
+            used += width;
+            used <= budget
+        }
+    };
+    let head = graphemes.iter().map(|g| g.width).take_while(fits(head_budget)).count();
+    let tail = graphemes[head..]
+        .iter()
+        .rev()
+        .map(|g| g.width)
+        .take_while(fits(tail_budget))
+        .count();
+    let tail_start = graphemes.len() - tail;
+
+    let mut spans = Vec::new();
+    push_graphemes(&mut spans, rich, &graphemes[..head]);
+    if let Some(ellipsis) = ellipsis {
+        // The ellipsis takes the style of the text it replaces next to the cut
+        let cut = match ellipsis {
+            Ellipsis::Start => &graphemes[tail_start - 1],
+            _ => &graphemes[head],
+        };
+        spans.push(Span::new(ELLIPSIS, rich.spans[cut.span].style.clone()));
+    }
+    push_graphemes(&mut spans, rich, &graphemes[tail_start..]);
+    RichString::new(spans)
+}
+
+/// A grapheme cluster of a span's text.
+struct Grapheme {
+    span: usize,
+    range: Range<usize>,
+    width: usize,
+}
+
+/// Splits the text of `rich` into graphemes.
+fn split_graphemes(rich: &RichString<'_>) -> Vec<Grapheme> {
+    let mut result = Vec::new();
+    for (span, source) in rich.spans.iter().enumerate() {
+        if source.is_rule() {
+            // A rule is cut like the line of `─` it is displayed as
+            result.extend((0..DEFAULT_WIDTH).map(|_| Grapheme { span, range: 0..0, width: 1 }));
+            continue;
+        }
+        let mut offset = 0;
+        for (grapheme, width) in graphemes(&source.text) {
+            let range = offset..offset + grapheme.len();
+            offset = range.end;
+            result.push(Grapheme { span, range, width });
+        }
+    }
+    result
+}
+
+/// Appends the text of `graphemes` to `spans`, one span for each run of
+/// graphemes that are next to each other in the same span of `rich`.
+fn push_graphemes<'a>(spans: &mut Vec<Span<'a>>, rich: &RichString<'a>, graphemes: &[Grapheme]) {
+    for run in graphemes.chunk_by(|a, b| a.span == b.span && a.range.end == b.range.start) {
+        let source = &rich.spans[run[0].span];
+        if source.is_rule() {
+            spans.push(Span::new(rule(run.len()), source.style.clone()));
+            continue;
+        }
+        let range = run[0].range.start..run[run.len() - 1].range.end;
+        let text = match &source.text {
+            Cow::Borrowed(text) => Cow::Borrowed(&text[range]),
+            Cow::Owned(text) => Cow::Owned(text[range].to_string()),
+        };
+        spans.push(Span::new(text, source.style.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+    use crate::style::{Color, Style};
+
+    #[test]
+    fn test_truncate_positions() {
+        let rs = RichString::parse("long <b>text</b> here");
+        assert_eq!(rs.truncate(8, Ellipsis::End).plain().to_string(), "long te…");
+        assert_eq!(rs.truncate(8, Ellipsis::Start).plain().to_string(), "…xt here");
+        assert_eq!(rs.truncate(8, Ellipsis::Middle).plain().to_string(), "long…ere");
+        assert_eq!(rs.truncate(14, Ellipsis::End), rs);
+        assert_eq!(rs.truncate(1, Ellipsis::End).plain().to_string(), "…");
+        assert!(rs.truncate(0, Ellipsis::End).spans.is_empty());
+    }
+
+    #[test]
+    fn test_truncate_ellipsis_style() {
+        let rs = RichString::parse("<red>abc</red><b>def</b>");
+        let end = rs.truncate(5, Ellipsis::End);
+        assert_eq!(end.spans.len(), 3);
+        assert_eq!(end.spans[2].text, "…");
+        assert_eq!(end.spans[2].style, Style::new().bold());
+
+        let start = rs.truncate(3, Ellipsis::Start);
+        assert_eq!(start.spans[0].style, Style::new().bold());
+        assert_eq!(start.plain().to_string(), "…ef");
+
+        let start = rs.truncate(4, Ellipsis::Start);
+        assert_eq!(start.spans[0].style, Style::new().fg(Color::Red));
+    }
+
+    #[test]
+    fn test_truncate_wide_and_combining() {
+        let rs = RichString::parse("日本語テキスト");
+        assert_eq!(rs.truncate(6, Ellipsis::End).plain().to_string(), "日本…");
+        assert_eq!(rs.truncate(6, Ellipsis::End).width(), 5);
+        let rs = RichString::parse("e\u{301}e\u{301}e\u{301}");
+        assert_eq!(rs.truncate(2, Ellipsis::End).plain().to_string(), "e\u{301}…");
+    }
+
+    #[test]
+    fn test_truncate_links_and_escapes() {
+        let rs = RichString::parse("<link=https://x.dev>documentation</link>");
+        assert_eq!(
+            rs.truncate(5, Ellipsis::End).to_string(),
+            "\x1b]8;;https://x.dev\x1b\\docu…\x1b]8;;\x1b\\\x1b[0m"
+        );
+        let rs = RichString::new(vec![Span::new("ab\x1b[1mcd\x1b[0mef", Style::new())]);
+        assert_eq!(rs.truncate(3, Ellipsis::End).to_string(), "ab…");
+        assert_eq!(rs.truncate(5, Ellipsis::Middle).to_string(), "ab…ef");
+    }
+
+    #[test]
+    fn test_truncate_rule() {
+        let rs = RichString::parse("a<b><hr/></b>");
+        let end = rs.truncate(12, Ellipsis::End);
+        assert_eq!(end.plain().to_string(), format!("a{}…", "─".repeat(10)));
+        assert_eq!(end.width(), 12);
+        assert_eq!(end.spans[1].style, Style::new().bold());
+        assert_eq!(rs.truncate(12, Ellipsis::Middle).width(), 12);
+        assert_eq!(rs.truncate(81, Ellipsis::End), rs);
+    }
+}
@@ -0,0 +1,101 @@
+//! Measuring the display width of text in terminal columns.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use crate::ansi::escape_len;
+
+/// Splits `text` into grapheme clusters paired with their width in columns.
+///
+/// Escape sequences are yielded whole with a width of 0, as are control
+/// characters. Wide characters such as CJK ideographs and most emoji are 2
+/// columns; combining marks and zero-width joiners add nothing to the cluster
+/// they belong to.
+pub(crate) fn graphemes(text: &str) -> impl Iterator<Item = (&str, usize)> {
+    let mut rest = text;
+    // Clusters of the text up to the next escape, so an escape is never merged into one
+    let mut clusters = "".graphemes(true);
+    std::iter::from_fn(move || {
+        if let Some(grapheme) = clusters.next() {
+            return Some((grapheme, grapheme_width(grapheme)));
+        }
+        if rest.is_empty() {
+            return None;
+        }
+        if rest.starts_with('\x1b') {
+            let (escape, after) = rest.split_at(escape_len(rest));
+            rest = after;
+            return Some((escape, 0));
+        }
+        let end = rest.find('\x1b').unwrap_or(rest.len());
+        let (text, after) = rest.split_at(end);
+        rest = after;
+        clusters = text.graphemes(true);
+        clusters.next().map(|grapheme| (grapheme, grapheme_width(grapheme)))
+    })
+}
+
+/// Returns the width of a single grapheme cluster.
+fn grapheme_width(grapheme: &str) -> usize {
+    if grapheme.chars().all(char::is_control) {
+        0
+    } else {
+        grapheme.width().min(2)
+    }
+}
+
+/// Returns the width of the widest line of `text`, ignoring escape sequences.
+pub(crate) fn str_width(text: &str) -> usize {
+    let mut widest = 0;
+    let mut line = 0;
+    measure(text, &mut line, &mut widest);
+    widest.max(line)
+}
+
+/// Adds the width of `text` to the current `line`, moving to a new line at
+/// each `\n` and recording the widest line seen in `widest`.
+pub(crate) fn measure(text: &str, line: &mut usize, widest: &mut usize) {
+    for (grapheme, width) in graphemes(text) {
+        if grapheme == "\n" || grapheme == "\r\n" {
+            *widest = (*widest).max(*line);
+            *line = 0;
+        } else {
+            *line += width;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_str_width() {
+        assert_eq!(str_width(""), 0);
+        assert_eq!(str_width("hello"), 5);
+        assert_eq!(str_width("日本語"), 6);
+        assert_eq!(str_width("e\u{301}"), 1);
+        assert_eq!(str_width("👨\u{200d}👩\u{200d}👧 ok"), 5);
+        assert_eq!(str_width("❤\u{fe0f}"), 2);
+        assert_eq!(str_width("🇺🇸"), 2);
+        assert_eq!(str_width("a\u{200b}b"), 2);
+    }
+
+    #[test]
+    fn test_str_width_ignores_escapes_and_controls() {
+        assert_eq!(str_width("\x1b[1;31mred\x1b[0m"), 3);
+        assert_eq!(str_width("\x1b]8;;https://x.dev\x1b\\link\x1b]8;;\x1b\\"), 4);
+        assert_eq!(str_width("a\x07b\rc"), 3);
+    }
+
+    #[test]
+    fn test_str_width_lines() {
+        assert_eq!(str_width("ab\nabcd\r\nabc"), 4);
+        assert_eq!(str_width("\n\n"), 0);
+    }
+
+    #[test]
+    fn test_graphemes() {
+        let parts: Vec<_> = graphemes("a\x1b[1m中e\u{301}").collect();
+        assert_eq!(parts, vec![("a", 1), ("\x1b[1m", 0), ("中", 2), ("e\u{301}", 1)]);
+    }
+}
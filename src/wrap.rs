@@ -0,0 +1,307 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use crate::parser::RichString;
+use crate::span::Span;
+use crate::style::Style;
+use crate::width::graphemes;
+
+/// Options controlling how [`RichString::wrap_with`] breaks text into lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrapOptions {
+    /// The maximum width of a line in columns.
+    pub width: usize,
+    /// Whether a word longer than a line is broken between characters; when
+    /// `false` it is put on a line of its own and overflows.
+    pub break_long_words: bool,
+    /// Whether continuation lines repeat the leading whitespace of the line they continue.
+    pub preserve_indent: bool,
+    /// Columns of indentation added to continuation lines, e.g. to line text up
+    /// after a bullet.
+    pub hanging_indent: usize,
+}
+
+impl WrapOptions {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            break_long_words: true,
+            preserve_indent: false,
+            hanging_indent: 0,
+        }
+    }
+
+    pub fn break_long_words(mut self, enabled: bool) -> Self {
+        self.break_long_words = enabled;
+        self
+    }
+
+    pub fn preserve_indent(mut self, enabled: bool) -> Self {
+        self.preserve_indent = enabled;
+        self
+    }
+
+    pub fn hanging_indent(mut self, columns: usize) -> Self {
+        self.hanging_indent = columns;
+        self
+    }
+}
+
+/// A grapheme cluster of a span's text.
+struct Grapheme {
+    span: usize,
+    range: Range<usize>,
+    width: usize,
+    space: bool,
+}
+
+/// A piece of an output line.
+#[derive(Clone)]
+enum Part<'a> {
+    /// Text taken from a span of the input.
+    Text { span: usize, range: Range<usize> },
+    /// Unstyled indentation.
+    Indent(Cow<'a, str>),
+}
+
+/// An output line being filled.
+struct Line<'a> {
+    parts: Vec<Part<'a>>,
+    width: usize,
+    /// Whether any text other than indentation has been added.
+    has_content: bool,
+}
+
+impl<'a> Line<'a> {
+    fn new(indent: &[Part<'a>], width: usize) -> Self {
+        Self {
+            parts: indent.to_vec(),
+            width,
+            has_content: false,
+        }
+    }
+
+    fn push(&mut self, grapheme: &Grapheme) {
+        self.width += grapheme.width;
+        // Extend the previous part when the grapheme directly follows it
+        if let Some(Part::Text { span, range }) = self.parts.last_mut()
+            && *span == grapheme.span
+            && range.end == grapheme.range.start
+        {
+            range.end = grapheme.range.end;
+            return;
+        }
+        self.parts.push(Part::Text {
+            span: grapheme.span,
+            range: grapheme.range.clone(),
+        });
+    }
+
+    fn finish(self, rich: &RichString<'a>) -> RichString<'a> {
+        let spans = self
+            .parts
+            .into_iter()
+            .map(|part| match part {
+                Part::Text { span, range } => {
+                    let source = &rich.spans[span];
+                    if source.is_rule() {
+                        return Span::rule(source.style.clone());
+                    }
+                    let text = match &source.text {
+                        Cow::Borrowed(text) => Cow::Borrowed(&text[range]),
+                        Cow::Owned(text) => Cow::Owned(text[range].to_string()),
+                    };
+                    Span::new(text, source.style.clone())
+                }
+                Part::Indent(text) => Span::new(text, Style::default()),
+            })
+            .collect();
+        RichString::new(spans)
+    }
+}
+
+/// Wraps `rich` to `options.width` columns; see [`RichString::wrap_with`].
+pub(crate) fn wrap<'a>(rich: &RichString<'a>, options: &WrapOptions) -> Vec<RichString<'a>> {
+    let mut lines = Vec::new();
+    for hard_line in hard_lines(rich) {
+        wrap_line(rich, &hard_line, options, &mut lines);
+    }
+    lines
+}
+
+/// Splits the text of `rich` into graphemes, one list per line of input.
+fn hard_lines(rich: &RichString<'_>) -> Vec<Vec<Grapheme>> {
+    let mut lines = vec![Vec::new()];
+    for (span, source) in rich.spans.iter().enumerate() {
+        if source.is_rule() {
+            // A rule stretches to the output width, so it is carried over whole
+            if let Some(line) = lines.last_mut() {
+                line.push(Grapheme {
+                    span,
+                    range: 0..0,
+                    width: 0,
+                    space: false,
+                });
+            }
+            continue;
+        }
+        let mut offset = 0;
+        for (grapheme, width) in graphemes(&source.text) {
+            let range = offset..offset + grapheme.len();
+            offset = range.end;
+            if grapheme == "\n" || grapheme == "\r\n" {
+                lines.push(Vec::new());
+                continue;
+            }
+            if let Some(line) = lines.last_mut() {
+                line.push(Grapheme {
+                    span,
+                    range,
+                    width,
+                    space: is_break(grapheme),
+                });
+            }
+        }
+    }
+    lines
+}
+
+/// Returns `true` for whitespace that allows a line break; non-breaking spaces do not.
+fn is_break(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .all(|c| c.is_whitespace() && !matches!(c, '\u{a0}' | '\u{2007}' | '\u{202f}'))
+}
+
+/// Wraps a single line of input, appending the resulting lines to `out`.
+fn wrap_line<'a>(rich: &RichString<'a>, graphemes: &[Grapheme], options: &WrapOptions, out: &mut Vec<RichString<'a>>) {
+    // Runs of whitespace and of word characters
+    let mut tokens: Vec<&[Grapheme]> = Vec::new();
+    let mut start = 0;
+    for i in 1..=graphemes.len() {
+        if i == graphemes.len() || graphemes[i].space != graphemes[start].space {
+            tokens.push(&graphemes[start..i]);
+            start = i;
+        }
+    }
+    let width_of = |token: &[Grapheme]| token.iter().map(|g| g.width).sum::<usize>();
+    let is_space = |token: &[Grapheme]| token.first().is_some_and(|g| g.space);
+
+    // Leading whitespace stays on the first line and may be repeated on the others
+    let leading: &[Grapheme] = match tokens.first() {
+        Some(token) if is_space(token) => tokens.remove(0),
+        _ => &[],
+    };
+    let mut indent = Vec::new();
+    let mut indent_width = 0;
+    if options.hanging_indent > 0 {
+        indent.push(Part::Indent(Cow::Owned(" ".repeat(options.hanging_indent))));
+        indent_width += options.hanging_indent;
+    }
+    if options.preserve_indent {
+        let mut leading_line = Line::new(&[], 0);
+        leading.iter().for_each(|g| leading_line.push(g));
+        indent.extend(leading_line.parts);
+        indent_width += width_of(leading);
+    }
+
+    let mut line = Line::new(&[], 0);
+    leading.iter().for_each(|g| line.push(g));
+    let mut pending: &[Grapheme] = &[];
+
+    for token in tokens {
+        if is_space(token) {
+            pending = token;
+            continue;
+        }
+        let word_width = width_of(token);
+        if line.has_content && line.width + width_of(pending) + word_width > options.width {
+            out.push(std::mem::replace(&mut line, Line::new(&indent, indent_width)).finish(rich));
+        } else if line.has_content {
+            pending.iter().for_each(|g| line.push(g));
+        }
+        pending = &[];
+
+        if options.break_long_words && line.width + word_width > options.width {
+            for grapheme in token {
+                if line.has_content && line.width + grapheme.width > options.width {
+                    out.push(std::mem::replace(&mut line, Line::new(&indent, indent_width)).finish(rich));
+                }
+                line.push(grapheme);
+                line.has_content = true;
+            }
+        } else {
+            token.iter().for_each(|g| line.push(g));
+            line.has_content = true;
+        }
+    }
+
+    out.push(line.finish(rich));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(lines: &[RichString<'_>]) -> Vec<String> {
+        lines.iter().map(|line| line.plain().to_string()).collect()
+    }
+
+    #[test]
+    fn test_wrap_words() {
+        let rs = RichString::parse("the quick <b>brown</b> fox jumps over the lazy dog");
+        assert_eq!(plain(&rs.wrap(10)), ["the quick", "brown fox", "jumps over", "the lazy", "dog"]);
+        assert_eq!(plain(&rs.wrap(100)), ["the quick brown fox jumps over the lazy dog"]);
+    }
+
+    #[test]
+    fn test_wrap_keeps_styles_across_spans() {
+        let rs = RichString::parse("<red>aa b</red><b>b cc</b>");
+        let lines = rs.wrap(4);
+        assert_eq!(plain(&lines), ["aa", "bb", "cc"]);
+        assert_eq!(lines[1].spans.len(), 2);
+        assert_eq!(lines[1].spans[0].style, Style::new().fg(crate::Color::Red));
+        assert_eq!(lines[1].spans[1].style, Style::new().bold());
+        assert!(matches!(lines[0].spans[0].text, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_wrap_reopens_links() {
+        let rs = RichString::parse("see <link=https://x.dev>the full docs</link>");
+        let lines = rs.wrap(8);
+        assert_eq!(plain(&lines), ["see the", "full", "docs"]);
+        assert_eq!(lines[1].spans[0].style.url.as_deref(), Some("https://x.dev"));
+        assert_eq!(lines[1].to_string(), "\x1b]8;;https://x.dev\x1b\\full\x1b]8;;\x1b\\\x1b[0m");
+    }
+
+    #[test]
+    fn test_wrap_long_words() {
+        let rs = RichString::parse("a abcdefghij b");
+        assert_eq!(plain(&rs.wrap(4)), ["a", "abcd", "efgh", "ij b"]);
+        let options = WrapOptions::new(4).break_long_words(false);
+        assert_eq!(plain(&rs.wrap_with(&options)), ["a", "abcdefghij", "b"]);
+    }
+
+    #[test]
+    fn test_wrap_wide_characters() {
+        let rs = RichString::parse("日本語 テキスト");
+        assert_eq!(plain(&rs.wrap(6)), ["日本語", "テキス", "ト"]);
+    }
+
+    #[test]
+    fn test_wrap_indent() {
+        let rs = RichString::parse("  - one two three\nnext");
+        let options = WrapOptions::new(9).preserve_indent(true).hanging_indent(2);
+        assert_eq!(plain(&rs.wrap_with(&options)), ["  - one", "    two", "    three", "next"]);
+        assert_eq!(plain(&rs.wrap(9)), ["  - one", "two three", "next"]);
+    }
+
+    #[test]
+    fn test_wrap_lines() {
+        let rs = RichString::parse("a\n\nb  \n");
+        assert_eq!(plain(&rs.wrap(5)), ["a", "", "b", ""]);
+        let owned = RichString::parse("<b>xy z</b>").into_owned();
+        assert_eq!(plain(&owned.wrap(2)), ["xy", "z"]);
+        let lines = RichString::parse("ab<br><hr/>").wrap(5);
+        assert_eq!(lines[1].spans, [Span::rule(Style::default())]);
+    }
+}
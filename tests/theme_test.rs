@@ -0,0 +1,97 @@
+use richparse::{Color, RichString, Theme, ThemeError};
+
+fn theme(name: &str, inherits: Option<&str>, styles: &[(&str, &str)]) -> Theme {
+    let mut theme = Theme::new();
+    theme.name = Some(name.to_string());
+    theme.inherits = inherits.map(str::to_string);
+    for (tag, style) in styles {
+        theme.insert(*tag, *style);
+    }
+    theme
+}
+
+#[test]
+fn test_theme_inheritance() {
+    let base = theme("base", None, &[("error", "red"), ("warning", "yellow")]);
+    let middle = theme("middle", Some("base"), &[("warning", "bright yellow")]);
+    let child = theme("child", Some("middle"), &[("error", "bold red")]);
+
+    let resolved = child.resolve(&[base, middle]).unwrap();
+    assert_eq!(resolved.inherits, None);
+    let registry = resolved.registry().unwrap();
+    let rs = RichString::parse_with(&registry, "<error>e</error><warning>w</warning>");
+    assert_eq!(rs.spans[0].style.fg, Color::Red);
+    assert!(rs.spans[0].style.bold);
+    assert_eq!(rs.spans[1].style.fg, Color::BrightYellow);
+}
+
+#[test]
+fn test_theme_resolve_errors() {
+    let a = theme("a", Some("b"), &[]);
+    let b = theme("b", Some("a"), &[]);
+    let err = a.resolve(&[a.clone(), b]).unwrap_err();
+    assert!(matches!(err, ThemeError::InheritanceCycle(ref name) if name == "a"));
+    assert_eq!(err.to_string(), "inheritance cycle through theme `a`");
+
+    let orphan = theme("orphan", Some("missing"), &[]);
+    assert!(matches!(orphan.resolve(&[]), Err(ThemeError::UnknownParent(_))));
+
+    let broken = theme("broken", None, &[("error", "bodl red")]);
+    assert!(matches!(broken.registry(), Err(ThemeError::InvalidStyle { .. })));
+}
+
+#[cfg(feature = "theme")]
+#[test]
+fn test_theme_from_toml() {
+    use richparse::{Color, RichString, Style, Theme};
+
+    let theme = Theme::from_toml(r##"
+        name = "dark"
+
+        [styles]
+        error = "bold red on #202020"
+        path = "cyan underline"
+    "##).unwrap();
+
+    assert_eq!(theme.name.as_deref(), Some("dark"));
+
+    let registry = theme.registry().unwrap();
+    let rs = RichString::parse_with(&registry, "<error>Cannot open <path>app.toml</path></error>");
+    assert_eq!(rs.spans[0].style, Style::new().bold().fg(Color::Red).bg(Color::Rgb(0x20, 0x20, 0x20)));
+    assert_eq!(rs.spans[1].style, Style::new().bold().fg(Color::Cyan).bg(Color::Rgb(0x20, 0x20, 0x20)).underline());
+}
+
+#[cfg(feature = "theme")]
+#[test]
+fn test_theme_from_json_with_inheritance() {
+    use richparse::{Color, RichString, Theme};
+
+    let base = Theme::from_json(r#"{ "name": "base", "styles": { "error": "red", "warning": "yellow" } }"#).unwrap();
+    let child = Theme::from_json(r#"{ "name": "child", "inherits": "base", "styles": { "error": "bright red bold" } }"#).unwrap();
+
+    let resolved = child.resolve(&[base]).unwrap();
+    assert_eq!(resolved.inherits, None);
+
+    let registry = resolved.registry().unwrap();
+    let rs = RichString::parse_with(&registry, "<error>e</error><warning>w</warning>");
+    assert_eq!(rs.spans[0].style.fg, Color::BrightRed);
+    assert!(rs.spans[0].style.bold);
+    assert_eq!(rs.spans[1].style.fg, Color::Yellow);
+}
+
+#[cfg(feature = "theme")]
+#[test]
+fn test_theme_errors() {
+    use richparse::{ThemeError, Theme};
+
+    let looping = Theme::from_toml("name = \"a\"\ninherits = \"a\"").unwrap();
+    assert!(matches!(looping.resolve(std::slice::from_ref(&looping)), Err(ThemeError::InheritanceCycle(_))));
+
+    let orphan = Theme::from_toml("inherits = \"missing\"").unwrap();
+    assert!(matches!(orphan.resolve(&[]), Err(ThemeError::UnknownParent(_))));
+
+    let broken = Theme::from_toml("[styles]\nerror = \"bodl red\"").unwrap();
+    assert!(matches!(broken.registry(), Err(ThemeError::InvalidStyle { .. })));
+
+    assert!(matches!(Theme::from_toml("styles = 3"), Err(ThemeError::Toml(_))));
+}